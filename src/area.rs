@@ -0,0 +1,627 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::data::{
+    node::NodeId,
+    relation::{Member, Relation, RelationId},
+    way::{Way, WayId},
+};
+use crate::location::{Location, LocationStore, WayGeometryResolver};
+
+/// The element an [`Area`] was built from.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AreaId {
+    Way(WayId),
+    Relation(RelationId),
+}
+
+/// A closed ring of nodes; the first and the last node are the same.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Ring {
+    pub nodes: Vec<NodeId>,
+    pub locations: Vec<Location>,
+}
+
+impl Ring {
+    /// Twice the signed area in square nanodegrees, positive when counter-clockwise.
+    fn signed_area2(&self) -> f64 {
+        self.locations
+            .windows(2)
+            .map(|w| {
+                let (a, b) = (w[0], w[1]);
+                a.nano_lon as f64 * b.nano_lat as f64 - b.nano_lon as f64 * a.nano_lat as f64
+            })
+            .sum()
+    }
+
+    #[inline]
+    fn is_counter_clockwise(&self) -> bool {
+        self.signed_area2() > 0.0
+    }
+
+    fn reverse(&mut self) {
+        self.nodes.reverse();
+        self.locations.reverse();
+    }
+
+    /// Returns `true` when the location is inside the ring (ray casting).
+    pub fn contains(&self, point: Location) -> bool {
+        let (x, y) = (point.nano_lon as f64, point.nano_lat as f64);
+        let mut inside = false;
+        for w in self.locations.windows(2) {
+            let (x1, y1) = (w[0].nano_lon as f64, w[0].nano_lat as f64);
+            let (x2, y2) = (w[1].nano_lon as f64, w[1].nano_lat as f64);
+            if (y1 > y) != (y2 > y) && x < (x2 - x1) * (y - y1) / (y2 - y1) + x1 {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+
+    /// Returns `true` when `other` lies inside this ring, tested on a node
+    /// that is not shared by both rings.
+    fn contains_ring(&self, other: &Ring) -> bool {
+        let nodes: HashSet<NodeId> = self.nodes.iter().copied().collect();
+        other
+            .nodes
+            .iter()
+            .zip(&other.locations)
+            .find(|(id, _)| !nodes.contains(id))
+            .is_some_and(|(_, &location)| self.contains(location))
+    }
+
+    /// The first node visited twice (apart from the closing node).
+    fn touching_node(&self) -> Option<NodeId> {
+        let mut seen = HashSet::new();
+        self.nodes[1..].iter().copied().find(|&id| !seen.insert(id))
+    }
+}
+
+/// An outer ring (counter-clockwise) with its inner rings (clockwise).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Polygon {
+    pub outer: Ring,
+    pub inners: Vec<Ring>,
+}
+
+impl Polygon {
+    /// Returns `true` when the location is inside the outer ring, but not
+    /// inside any of the inner rings.
+    pub fn contains(&self, point: Location) -> bool {
+        self.outer.contains(point) && !self.inners.iter().any(|r| r.contains(point))
+    }
+}
+
+/// A problem found while assembling an area.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Problem {
+    /// A member way was not found in the second pass.
+    MissingWay(WayId),
+    /// Nodes of a member way without a location; the way is skipped.
+    MissingNodes(WayId, Vec<NodeId>),
+    /// Ring fragments that could not be closed, from the first to the last node.
+    UnclosedRing(NodeId, NodeId),
+    /// A ring visits the node more than once.
+    SelfTouchingRing(NodeId),
+    /// An inner ring that is not inside any outer ring; it is skipped.
+    InnerWithoutOuter(NodeId),
+}
+
+/// A polygon or multipolygon built from a closed way or a relation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Area {
+    pub id: AreaId,
+    pub tags: Vec<(String, String)>,
+    pub polygons: Vec<Polygon>,
+    pub problems: Vec<Problem>,
+}
+
+impl Area {
+    /// Returns `true` when the area was assembled without problems.
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty() && !self.polygons.is_empty()
+    }
+}
+
+/// Keys that make a closed way an area (unless tagged `area=no`).
+const AREA_KEYS: &[&str] = &[
+    "aeroway",
+    "amenity",
+    "building",
+    "building:part",
+    "craft",
+    "historic",
+    "landuse",
+    "leisure",
+    "man_made",
+    "military",
+    "natural",
+    "office",
+    "place",
+    "shop",
+    "tourism",
+    "water",
+];
+
+/// Default filter for closed ways, see [`AreaAssembler::with_area_filter`].
+///
+/// Accepts `area=yes` and the common area keys, but not `natural=coastline`
+/// or `area=no`.
+pub fn has_area_tags(tags: &[(&str, &str)]) -> bool {
+    let mut area = false;
+    for &(k, v) in tags {
+        match (k, v) {
+            ("area", "no") | ("natural", "coastline") => return false,
+            ("area", "yes") => area = true,
+            (k, _) if AREA_KEYS.contains(&k) => area = true,
+            _ => {}
+        }
+    }
+    area
+}
+
+struct PendingRelation {
+    id: RelationId,
+    tags: Vec<(String, String)>,
+    outer: Vec<WayId>,
+    inner: Vec<WayId>,
+}
+
+enum MemberWay {
+    Pending,
+    Found(Vec<NodeId>, Vec<Location>),
+    MissingNodes(Vec<NodeId>),
+}
+
+/// Assembles areas from multipolygon and boundary relations in two passes.
+///
+/// In the first pass, pass all relations to [`AreaAssembler::add_relation`].
+/// In the second pass, fill the [`LocationStore`] with the nodes and pass
+/// all ways to [`AreaAssembler::add_way`], which directly returns areas for
+/// closed ways with area tags. [`AreaAssembler::finish`] then returns the
+/// areas of the relations.
+pub struct AreaAssembler {
+    relations: Vec<PendingRelation>,
+    ways: HashMap<WayId, MemberWay>,
+    area_filter: fn(&[(&str, &str)]) -> bool,
+}
+
+impl Default for AreaAssembler {
+    fn default() -> Self {
+        Self {
+            relations: Vec::new(),
+            ways: HashMap::new(),
+            area_filter: has_area_tags,
+        }
+    }
+}
+
+impl AreaAssembler {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the filter deciding which closed ways are areas (default: [`has_area_tags`]).
+    #[inline]
+    pub fn with_area_filter(mut self, area_filter: fn(&[(&str, &str)]) -> bool) -> Self {
+        self.area_filter = area_filter;
+        self
+    }
+
+    /// First pass: remembers a `type=multipolygon` or `type=boundary` relation.
+    ///
+    /// Returns `false` when the relation is ignored. Way members with the role
+    /// `inner` are inner rings, `outer` or an empty role are outer rings.
+    pub fn add_relation(&mut self, relation: &Relation<'_>) -> bool {
+        if !relation
+            .tags()
+            .any(|(k, v)| k == "type" && (v == "multipolygon" || v == "boundary"))
+        {
+            return false;
+        }
+        let mut pending = PendingRelation {
+            id: relation.id,
+            tags: relation
+                .tags()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            outer: Vec::new(),
+            inner: Vec::new(),
+        };
+        for member in relation.members() {
+            let id = match member {
+                Member::Way(id, "outer" | "") => {
+                    pending.outer.push(id);
+                    id
+                }
+                Member::Way(id, "inner") => {
+                    pending.inner.push(id);
+                    id
+                }
+                _ => continue,
+            };
+            self.ways.entry(id).or_insert(MemberWay::Pending);
+        }
+        self.relations.push(pending);
+        true
+    }
+
+    /// Returns `true` when the way is a member of a relation from the first pass.
+    #[inline]
+    pub fn is_member_way(&self, id: WayId) -> bool {
+        self.ways.contains_key(&id)
+    }
+
+    /// Second pass: records the geometry of member ways and returns the
+    /// area of a closed way with area tags.
+    pub fn add_way<S: LocationStore>(
+        &mut self,
+        way: &Way<'_>,
+        resolver: &WayGeometryResolver<S>,
+    ) -> Option<Area> {
+        let refs: Vec<NodeId> = way.refs().collect();
+        let closed = refs.len() >= 4 && refs.first() == refs.last();
+        let is_area = closed && {
+            let tags: Vec<(&str, &str)> = way.tags().collect();
+            (self.area_filter)(&tags)
+        };
+        let member = self.ways.get_mut(&way.id);
+        if member.is_none() && !is_area {
+            return None;
+        }
+
+        let geometry = resolver.resolve(way);
+        if let Some(member) = member {
+            *member = if geometry.is_complete() {
+                MemberWay::Found(refs.clone(), geometry.locations.clone())
+            } else {
+                MemberWay::MissingNodes(geometry.missing.clone())
+            };
+        }
+        if !is_area {
+            return None;
+        }
+
+        let mut area = Area {
+            id: AreaId::Way(way.id),
+            tags: way
+                .tags()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            polygons: Vec::new(),
+            problems: Vec::new(),
+        };
+        if !geometry.is_complete() {
+            area.problems
+                .push(Problem::MissingNodes(way.id, geometry.missing));
+            return Some(area);
+        }
+        let mut ring = Ring {
+            nodes: refs,
+            locations: geometry.locations,
+        };
+        if let Some(node) = ring.touching_node() {
+            area.problems.push(Problem::SelfTouchingRing(node));
+        }
+        if !ring.is_counter_clockwise() {
+            ring.reverse();
+        }
+        area.polygons.push(Polygon {
+            outer: ring,
+            inners: Vec::new(),
+        });
+        Some(area)
+    }
+
+    /// Assembles the areas of all relations from the first pass.
+    pub fn finish(self) -> Vec<Area> {
+        let Self {
+            relations, ways, ..
+        } = self;
+        relations
+            .into_iter()
+            .map(|relation| assemble(relation, &ways))
+            .collect()
+    }
+}
+
+fn assemble(relation: PendingRelation, ways: &HashMap<WayId, MemberWay>) -> Area {
+    let mut problems = Vec::new();
+    let mut outers = stitch(&relation.outer, ways, &mut problems);
+    let inners = stitch(&relation.inner, ways, &mut problems);
+
+    for ring in &mut outers {
+        if !ring.is_counter_clockwise() {
+            ring.reverse();
+        }
+    }
+    let mut polygons: Vec<Polygon> = outers
+        .into_iter()
+        .map(|outer| Polygon {
+            outer,
+            inners: Vec::new(),
+        })
+        .collect();
+
+    for mut inner in inners {
+        if inner.is_counter_clockwise() {
+            inner.reverse();
+        }
+        // the smallest outer ring containing the inner ring
+        let outer = polygons
+            .iter_mut()
+            .filter(|p| p.outer.contains_ring(&inner))
+            .min_by(|a, b| a.outer.signed_area2().total_cmp(&b.outer.signed_area2()));
+        match outer {
+            Some(polygon) => polygon.inners.push(inner),
+            None => problems.push(Problem::InnerWithoutOuter(inner.nodes[0])),
+        }
+    }
+
+    Area {
+        id: AreaId::Relation(relation.id),
+        tags: relation.tags,
+        polygons,
+        problems,
+    }
+}
+
+/// Joins the ways at their end nodes to closed rings.
+fn stitch(
+    way_ids: &[WayId],
+    ways: &HashMap<WayId, MemberWay>,
+    problems: &mut Vec<Problem>,
+) -> Vec<Ring> {
+    let mut segments: Vec<Option<Ring>> = Vec::new();
+    for &id in way_ids {
+        match ways.get(&id) {
+            Some(MemberWay::Found(nodes, locations)) if nodes.len() >= 2 => {
+                segments.push(Some(Ring {
+                    nodes: nodes.clone(),
+                    locations: locations.clone(),
+                }))
+            }
+            Some(MemberWay::Found(..)) => {}
+            Some(MemberWay::MissingNodes(missing)) => {
+                problems.push(Problem::MissingNodes(id, missing.clone()))
+            }
+            Some(MemberWay::Pending) | None => problems.push(Problem::MissingWay(id)),
+        }
+    }
+
+    // segments by their end nodes
+    let mut ends: HashMap<NodeId, Vec<usize>> = HashMap::new();
+    for (i, segment) in segments.iter().enumerate() {
+        let segment = segment.as_ref().unwrap();
+        ends.entry(segment.nodes[0]).or_default().push(i);
+        ends.entry(*segment.nodes.last().unwrap())
+            .or_default()
+            .push(i);
+    }
+    let mut take_at = |node: NodeId, segments: &mut Vec<Option<Ring>>| {
+        let candidates = ends.get_mut(&node)?;
+        while let Some(i) = candidates.pop() {
+            if let Some(segment) = segments[i].take() {
+                return Some(segment);
+            }
+        }
+        None
+    };
+
+    let mut rings = Vec::new();
+    for i in 0..segments.len() {
+        let Some(mut ring) = segments[i].take() else {
+            continue;
+        };
+        // extend at the end, then at the start until the ring is closed
+        let mut backwards = false;
+        while ring.nodes.first() != ring.nodes.last() {
+            let end = *ring.nodes.last().unwrap();
+            let Some(mut next) = take_at(end, &mut segments) else {
+                if backwards {
+                    break;
+                }
+                backwards = true;
+                ring.reverse();
+                continue;
+            };
+            if next.nodes[0] != end {
+                next.reverse();
+            }
+            ring.nodes.extend_from_slice(&next.nodes[1..]);
+            ring.locations.extend_from_slice(&next.locations[1..]);
+        }
+        if ring.nodes.len() < 4 || ring.nodes.first() != ring.nodes.last() {
+            problems.push(Problem::UnclosedRing(
+                ring.nodes[0],
+                *ring.nodes.last().unwrap(),
+            ));
+            continue;
+        }
+        if let Some(node) = ring.touching_node() {
+            problems.push(Problem::SelfTouchingRing(node));
+        }
+        rings.push(ring);
+    }
+    rings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{builder::PrimitiveBlockBuilder, primitive::Primitive, Meta};
+    use crate::location::SparseLocationStore;
+
+    /// Nodes as `(id, lat, lon)` in hundredths of a degree.
+    const NODES: &[(i64, i64, i64)] = &[
+        // large square
+        (1, 0, 0),
+        (2, 0, 100),
+        (3, 100, 100),
+        (4, 100, 0),
+        // small square inside the large one
+        (11, 10, 10),
+        (12, 10, 40),
+        (13, 40, 40),
+        (14, 40, 10),
+        // inside the small square
+        (21, 20, 20),
+        (22, 20, 30),
+        (23, 30, 30),
+        (24, 30, 20),
+        // outside of both squares
+        (31, 200, 200),
+        (32, 200, 210),
+        (33, 210, 210),
+        (34, 210, 200),
+    ];
+
+    /// `(id, refs, tags)`
+    type TestWay<'a> = (i64, &'a [i64], &'a [(&'a str, &'a str)]);
+
+    fn ids(ids: &[i64]) -> Vec<NodeId> {
+        ids.iter().copied().map(NodeId).collect()
+    }
+
+    /// Runs both passes over the ways and one multipolygon with the
+    /// `(way id, role)` members, returns the areas of the ways and the relation.
+    fn assemble_areas(ways: &[TestWay<'_>], members: &[(i64, &str)]) -> (Vec<Area>, Area) {
+        let mut store = SparseLocationStore::new();
+        for &(id, lat, lon) in NODES {
+            let location = Location::new(lat * 10_000_000, lon * 10_000_000);
+            store.set(NodeId(id), location).unwrap();
+        }
+        let resolver = WayGeometryResolver::new(store);
+
+        let mut builder = PrimitiveBlockBuilder::new();
+        for &(id, refs, tags) in ways {
+            builder.add_way(WayId(id), ids(refs), tags.iter().copied(), &Meta::default());
+        }
+        builder.add_relation(
+            RelationId(1),
+            members
+                .iter()
+                .map(|&(id, role)| Member::Way(WayId(id), role)),
+            [("type", "multipolygon")],
+            &Meta::default(),
+        );
+        let block = builder.build();
+
+        let mut assembler = AreaAssembler::new();
+        for primitive in block.primitives() {
+            if let Primitive::Relation(relation) = primitive {
+                assert!(assembler.add_relation(&relation));
+            }
+        }
+        let mut areas = Vec::new();
+        for primitive in block.primitives() {
+            if let Primitive::Way(way) = primitive {
+                areas.extend(assembler.add_way(&way, &resolver));
+            }
+        }
+        let mut relations = assembler.finish();
+        assert_eq!(relations.len(), 1);
+        (areas, relations.pop().unwrap())
+    }
+
+    #[test]
+    fn closed_way_with_area_tags() {
+        let (areas, _) = assemble_areas(
+            &[
+                // clockwise, gets reversed
+                (1, &[1, 4, 3, 2, 1], &[("building", "yes")]),
+                (2, &[11, 12, 13, 14, 11], &[("highway", "pedestrian")]),
+                (
+                    3,
+                    &[21, 22, 23, 24, 21],
+                    &[("building", "yes"), ("area", "no")],
+                ),
+                (4, &[31, 32, 33], &[("building", "yes")]),
+            ],
+            &[],
+        );
+        assert_eq!(areas.len(), 1);
+        let area = &areas[0];
+        assert_eq!(area.id, AreaId::Way(WayId(1)));
+        assert_eq!(area.tags, [("building".to_string(), "yes".to_string())]);
+        assert!(area.is_valid());
+        assert_eq!(area.polygons.len(), 1);
+        let polygon = &area.polygons[0];
+        assert_eq!(polygon.outer.nodes, ids(&[1, 2, 3, 4, 1]));
+        assert!(polygon.outer.is_counter_clockwise());
+        assert!(polygon.inners.is_empty());
+        assert!(polygon.contains(Location::new(500_000_000, 500_000_000)));
+        assert!(!polygon.contains(Location::new(1_500_000_000, 500_000_000)));
+    }
+
+    #[test]
+    fn outer_ring_from_reversed_ways() {
+        let (areas, area) = assemble_areas(
+            &[
+                (1, &[1, 2], &[]),
+                (2, &[3, 2], &[]),
+                (3, &[3, 4], &[]),
+                (4, &[1, 4], &[]),
+            ],
+            &[(1, "outer"), (2, "outer"), (3, ""), (4, "outer")],
+        );
+        assert!(areas.is_empty());
+        assert_eq!(area.id, AreaId::Relation(RelationId(1)));
+        assert!(area.is_valid(), "{:?}", area.problems);
+        assert_eq!(area.polygons.len(), 1);
+        let outer = &area.polygons[0].outer;
+        assert_eq!(outer.nodes, ids(&[1, 2, 3, 4, 1]));
+        assert_eq!(outer.locations.len(), outer.nodes.len());
+        assert!(outer.is_counter_clockwise());
+    }
+
+    #[test]
+    fn inner_ring_in_smallest_outer() {
+        let (_, area) = assemble_areas(
+            &[
+                (1, &[1, 2, 3, 4, 1], &[]),
+                (2, &[11, 12, 13, 14, 11], &[]),
+                (3, &[21, 22, 23, 24, 21], &[]),
+            ],
+            &[(1, "outer"), (2, "outer"), (3, "inner")],
+        );
+        assert!(area.is_valid(), "{:?}", area.problems);
+        assert_eq!(area.polygons.len(), 2);
+        assert_eq!(area.polygons[0].outer.nodes[0], NodeId(1));
+        assert!(area.polygons[0].inners.is_empty());
+        assert_eq!(area.polygons[1].outer.nodes[0], NodeId(11));
+        // inner rings are clockwise
+        assert_eq!(area.polygons[1].inners.len(), 1);
+        let inner = &area.polygons[1].inners[0];
+        assert_eq!(inner.nodes, ids(&[21, 24, 23, 22, 21]));
+        assert!(!inner.is_counter_clockwise());
+        let hole = Location::new(250_000_000, 250_000_000);
+        assert!(!area.polygons[1].contains(hole));
+        assert!(area.polygons[0].contains(hole));
+    }
+
+    #[test]
+    fn problems() {
+        let (_, area) = assemble_areas(
+            &[(1, &[1, 2, 3], &[]), (3, &[31, 32, 33, 34, 31], &[])],
+            &[(1, "outer"), (2, "outer"), (3, "inner")],
+        );
+        assert!(!area.is_valid());
+        assert!(area.polygons.is_empty());
+        assert_eq!(
+            area.problems,
+            [
+                Problem::MissingWay(WayId(2)),
+                Problem::UnclosedRing(NodeId(3), NodeId(1)),
+                Problem::InnerWithoutOuter(NodeId(31)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,225 @@
+//! `osmpbf`: inspect `.osm.pbf` files.
+//!
+//! ```text
+//! osmpbf fileinfo FILE          header fields, blob count, compression and bbox
+//! osmpbf cat [-t nwrc] FILE     dumps the elements (of the given types) as OPL
+//! osmpbf stats FILE             counts elements & tags, min/max ids
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process::ExitCode;
+
+use osm_pbf_reader::data::primitive::{Primitive, PrimitiveType};
+use osm_pbf_reader::header::BoundingBox;
+use osm_pbf_reader::opl::OplWriter;
+use osm_pbf_reader::text::{format_nano_degrees, format_timestamp};
+use osm_pbf_reader::Blobs;
+
+type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const USAGE: &str = "\
+usage: osmpbf <command> [options] FILE
+
+commands:
+    fileinfo          print the header fields, blob count, compression and bbox
+    cat [-t TYPES]    dump the elements as OPL; TYPES is a combination of
+                      n(odes), w(ays), r(elations) and c(hangesets), default: nwr
+    stats             count nodes, ways, relations and tags, min/max ids";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["fileinfo", file] => fileinfo(file),
+        ["cat", file] => cat(file, PrimitiveType::DEFAULT),
+        ["cat", "-t", types, file] => match parse_types(types) {
+            Some(types) => cat(file, types),
+            None => return usage(),
+        },
+        ["stats", file] => stats(file),
+        _ => return usage(),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        // e.g. `osmpbf cat FILE | head`
+        Err(e)
+            if e.downcast_ref::<io::Error>().map(io::Error::kind)
+                == Some(io::ErrorKind::BrokenPipe) =>
+        {
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("osmpbf: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn usage() -> ExitCode {
+    eprintln!("{USAGE}");
+    ExitCode::from(2)
+}
+
+fn parse_types(s: &str) -> Option<PrimitiveType> {
+    let mut types = PrimitiveType::empty();
+    for c in s.chars() {
+        types |= match c {
+            'n' => PrimitiveType::NODE,
+            'w' => PrimitiveType::WAY,
+            'r' => PrimitiveType::RELATION,
+            'c' => PrimitiveType::CHANGE_SET,
+            _ => return None,
+        };
+    }
+    Some(types)
+}
+
+fn open(file: &str) -> BoxResult<Blobs<io::BufReader<File>>> {
+    let file = File::open(file).map_err(|e| format!("{file}: {e}"))?;
+    Ok(Blobs::from_read(file))
+}
+
+fn fileinfo(file: &str) -> BoxResult<()> {
+    let mut blobs = open(file)?;
+    let header_blob = blobs.header()?;
+    let header = header_blob.decode()?;
+    let mut out = io::stdout().lock();
+
+    writeln!(out, "File: {file}")?;
+    writeln!(out, "Header:")?;
+    let mut features: Vec<&str> = header.required_features().feature_names().collect();
+    features.extend(header.unknown_required_features());
+    writeln!(out, "  required features: {}", features.join(" "))?;
+    let mut features: Vec<&str> = header.optional_features().feature_names().collect();
+    features.extend(header.unknown_optional_features());
+    writeln!(out, "  optional features: {}", features.join(" "))?;
+    if let Some(program) = header.writing_program() {
+        writeln!(out, "  writing program: {program}")?;
+    }
+    if let Some(source) = header.source() {
+        writeln!(out, "  source: {source}")?;
+    }
+    if let Some(timestamp) = header.replication_timestamp() {
+        writeln!(
+            out,
+            "  replication timestamp: {}",
+            format_timestamp(timestamp * 1000)
+        )?;
+    }
+    if let Some(sequence_number) = header.replication_sequence_number() {
+        writeln!(out, "  replication sequence number: {sequence_number}")?;
+    }
+    if let Some(base_url) = header.replication_base_url() {
+        writeln!(out, "  replication base url: {base_url}")?;
+    }
+    match header.bbox() {
+        Some(bbox) => writeln!(out, "  bbox: {}", format_bbox(&bbox))?,
+        None => writeln!(out, "  bbox: -")?,
+    }
+
+    let mut count = 0u64;
+    let mut size = header_blob.datasize() as u64;
+    let mut encodings = BTreeMap::new();
+    *encodings.entry(header_blob.encoding()).or_insert(0u64) += 1;
+    for blob in blobs {
+        let blob = blob?;
+        count += 1;
+        size += blob.datasize() as u64;
+        *encodings.entry(blob.encoding()).or_insert(0) += 1;
+    }
+    writeln!(out, "Blobs:")?;
+    writeln!(out, "  data blobs: {count}")?;
+    writeln!(out, "  size: {size} bytes")?;
+    let encodings = encodings
+        .iter()
+        .map(|(encoding, count)| format!("{encoding}={count}"))
+        .collect::<Vec<_>>();
+    writeln!(out, "  compression: {}", encodings.join(" "))?;
+    Ok(())
+}
+
+fn cat(file: &str, types: PrimitiveType) -> BoxResult<()> {
+    let mut blobs = open(file)?;
+    blobs.header()?;
+    let mut writer = OplWriter::new(BufWriter::new(io::stdout().lock()));
+    for blob in blobs {
+        let block = blob?.decode_lazy()?;
+        for primitive in block.primitives().types(types) {
+            writer.write_primitive(&primitive?)?;
+        }
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[derive(Default)]
+struct TypeStats {
+    count: u64,
+    tags: u64,
+    min_id: Option<i64>,
+    max_id: Option<i64>,
+}
+
+impl TypeStats {
+    fn add(&mut self, id: i64, tags: usize) {
+        self.count += 1;
+        self.tags += tags as u64;
+        self.min_id = Some(self.min_id.map_or(id, |min| min.min(id)));
+        self.max_id = Some(self.max_id.map_or(id, |max| max.max(id)));
+    }
+}
+
+fn stats(file: &str) -> BoxResult<()> {
+    let mut blobs = open(file)?;
+    blobs.header()?;
+    let mut nodes = TypeStats::default();
+    let mut ways = TypeStats::default();
+    let mut relations = TypeStats::default();
+    let mut refs = 0u64;
+    let mut members = 0u64;
+    for blob in blobs {
+        let block = blob?.decode_lazy()?;
+        for primitive in block.primitives() {
+            match primitive? {
+                Primitive::Node(n) => nodes.add(n.id.0, n.tags().count()),
+                Primitive::Way(w) => {
+                    ways.add(w.id.0, w.tags().count());
+                    refs += w.refs().count() as u64;
+                }
+                Primitive::Relation(r) => {
+                    relations.add(r.id.0, r.tags().count());
+                    members += r.members().count() as u64;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut out = io::stdout().lock();
+    for (name, stats) in [
+        ("nodes", &nodes),
+        ("ways", &ways),
+        ("relations", &relations),
+    ] {
+        write!(out, "{name}: {} (tags: {}", stats.count, stats.tags)?;
+        if let (Some(min), Some(max)) = (stats.min_id, stats.max_id) {
+            write!(out, ", ids: {min}..={max}")?;
+        }
+        writeln!(out, ")")?;
+    }
+    writeln!(out, "way node refs: {refs}")?;
+    writeln!(out, "relation members: {members}")?;
+    writeln!(out, "tags: {}", nodes.tags + ways.tags + relations.tags)?;
+    Ok(())
+}
+
+fn format_bbox(bbox: &BoundingBox) -> String {
+    format!(
+        "({},{},{},{})",
+        format_nano_degrees(bbox.left),
+        format_nano_degrees(bbox.bottom),
+        format_nano_degrees(bbox.right),
+        format_nano_degrees(bbox.top)
+    )
+}
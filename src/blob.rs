@@ -1,17 +1,19 @@
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use bytes::Bytes;
+use osm_pbf_proto::fileformat::blob::Data as PbfBlobData;
 pub use osm_pbf_proto::fileformat::{Blob as PbfBlob, BlobHeader as PbfBlobHeader};
 use osm_pbf_proto::protobuf::{CodedInputStream, Message};
-use std::io::{self, BufRead, Read};
+use std::io::{self, BufRead, Read, Write};
 use std::iter;
 use std::marker::PhantomData;
 use std::ops::Deref;
 
-use crate::data::OSMDataBlob;
+use crate::data::{OSMDataBlob, PrimitiveBlock};
 use crate::error::{Error, Result};
-use crate::header::OSMHeaderBlob;
+use crate::header::{HeaderBlock, OSMHeaderBlob};
 
-const MAX_HEADER_SIZE: u32 = 64 * 1024;
-const MAX_UNCOMPRESSED_DATA_SIZE: usize = 32 * 1024 * 1024;
+pub(crate) const MAX_HEADER_SIZE: u32 = 64 * 1024;
+pub(crate) const MAX_UNCOMPRESSED_DATA_SIZE: usize = 32 * 1024 * 1024;
 
 pub struct Blob<M> {
     header: PbfBlobHeader,
@@ -21,7 +23,7 @@ pub struct Blob<M> {
 
 impl<M> Blob<M> {
     #[inline]
-    const fn new(header: PbfBlobHeader, blob: PbfBlob) -> Self {
+    pub(crate) const fn new(header: PbfBlobHeader, blob: PbfBlob) -> Self {
         Blob {
             header,
             blob,
@@ -30,11 +32,44 @@ impl<M> Blob<M> {
     }
 }
 
+/// Compression used for the data of encoded blobs.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// Store the data uncompressed (`raw`).
+    None,
+    /// zlib-compression with the given level (0-9).
+    #[cfg(feature = "zlib")]
+    Zlib(u32),
+    /// lzma-compression with the given preset (0-9).
+    #[cfg(feature = "lzma")]
+    Lzma(u32),
+}
+
+impl Default for Compression {
+    #[inline]
+    fn default() -> Self {
+        #[cfg(feature = "zlib")]
+        {
+            Self::Zlib(6)
+        }
+        #[cfg(not(feature = "zlib"))]
+        {
+            Self::None
+        }
+    }
+}
+
 pub trait Block: Sized {
+    /// The value of the `type` field in the `BlobHeader` of blobs containing this block.
+    const BLOB_TYPE: &'static str;
+
     type Message: Message;
 
     fn from_message(pbf: Self::Message) -> Result<Self>;
 
+    fn to_message(&self) -> Result<Self::Message>;
+
     #[inline]
     fn parse_from_reader(reader: &mut dyn Read) -> Result<Self> {
         let mut is = CodedInputStream::new(reader);
@@ -62,6 +97,18 @@ pub trait Block: Sized {
         Ok(block)
     }
 
+    /// Parses the block from a shared buffer.
+    ///
+    /// Bytes-fields of the message reference the buffer instead of being copied.
+    #[inline]
+    fn parse_from_tokio_bytes(bytes: &Bytes) -> Result<Self> {
+        let mut is = CodedInputStream::from_tokio_bytes(bytes);
+        let msg = Self::Message::parse_from(&mut is)?;
+        is.check_eof()?;
+        let block = Self::from_message(msg)?;
+        Ok(block)
+    }
+
     #[inline]
     fn parse_from(is: &mut CodedInputStream) -> Result<Self> {
         let msg = Self::Message::parse_from(is)?;
@@ -70,20 +117,113 @@ pub trait Block: Sized {
     }
 }
 
+#[inline]
+fn parse_message<T: Message>(mut is: CodedInputStream<'_>) -> Result<T> {
+    let msg = T::parse_from(&mut is)?;
+    is.check_eof()?;
+    Ok(msg)
+}
+
+/// Reads decompressed data, limited to [`MAX_UNCOMPRESSED_DATA_SIZE`].
+#[cfg(any(feature = "zlib", feature = "lzma"))]
+fn read_data(read: impl Read, raw_size: i32) -> Result<Bytes> {
+    let mut data = Vec::with_capacity((raw_size.max(0) as usize).min(MAX_UNCOMPRESSED_DATA_SIZE));
+    read.take(MAX_UNCOMPRESSED_DATA_SIZE as u64 + 1)
+        .read_to_end(&mut data)?;
+    if data.len() > MAX_UNCOMPRESSED_DATA_SIZE {
+        return Err(Error::BlobDataToLarge);
+    }
+    Ok(data.into())
+}
+
 impl<M: Block> Blob<M> {
+    /// Encodes the given block into a new blob using the given compression.
+    pub fn encode(block: &M, compression: Compression) -> Result<Self> {
+        let data = block.to_message()?.write_to_bytes()?;
+        if data.len() > MAX_UNCOMPRESSED_DATA_SIZE {
+            return Err(Error::BlobDataToLarge);
+        }
+        let mut blob = PbfBlob::new();
+        match compression {
+            Compression::None => blob.set_raw(data.into()),
+            #[cfg(feature = "zlib")]
+            Compression::Zlib(level) => {
+                let mut encoder = flate2::write::ZlibEncoder::new(
+                    Vec::with_capacity(data.len() / 2),
+                    flate2::Compression::new(level),
+                );
+                encoder.write_all(&data)?;
+                blob.set_raw_size(data.len() as i32);
+                blob.set_zlib_data(encoder.finish()?.into());
+            }
+            #[cfg(feature = "lzma")]
+            Compression::Lzma(preset) => {
+                let mut encoder =
+                    xz2::write::XzEncoder::new(Vec::with_capacity(data.len() / 2), preset);
+                encoder.write_all(&data)?;
+                blob.set_raw_size(data.len() as i32);
+                blob.set_lzma_data(encoder.finish()?.into());
+            }
+        }
+        let mut header = PbfBlobHeader::new();
+        header.set_type(M::BLOB_TYPE.into());
+        header.set_datasize(blob.compute_size() as i32);
+        Ok(Self::new(header, blob))
+    }
+
     pub fn decode(&self) -> Result<M> {
-        if self.blob.has_raw() {
-            Ok(M::parse_from_bytes(self.blob.raw())?)
-        } else if cfg!(feature = "zlib") && self.blob.has_zlib_data() {
-            let cursor = io::Cursor::new(self.blob.zlib_data());
-            let mut decoder = flate2::bufread::ZlibDecoder::new(cursor);
-            Ok(M::parse_from_reader(&mut decoder)?)
-        } else if cfg!(feature = "lzma") && self.blob.has_lzma_data() {
-            let cursor = io::Cursor::new(self.blob.lzma_data());
-            let mut decoder = xz2::bufread::XzDecoder::new(cursor);
-            Ok(M::parse_from_reader(&mut decoder)?)
-        } else {
-            Err(Error::UnsupportedEncoding)
+        M::from_message(self.decode_message()?)
+    }
+
+    /// Decompresses and parses the protobuf message of the block.
+    ///
+    /// The message is parsed from a single buffer, so its bytes fields
+    /// (like the strings of the string table) are slices of that buffer.
+    pub(crate) fn decode_message(&self) -> Result<M::Message> {
+        let data = self.decompress()?;
+        parse_message(CodedInputStream::from_tokio_bytes(&data))
+    }
+}
+
+impl<M> Blob<M> {
+    /// Decompresses the data of this blob.
+    ///
+    /// Uncompressed (`raw`) data is returned without copying.
+    pub fn decompress(&self) -> Result<Bytes> {
+        match self.blob.data {
+            Some(PbfBlobData::Raw(ref raw)) => Ok(raw.clone()),
+            #[cfg(feature = "zlib")]
+            Some(PbfBlobData::ZlibData(ref data)) => {
+                let decoder = flate2::bufread::ZlibDecoder::new(&data[..]);
+                read_data(decoder, self.blob.raw_size())
+            }
+            #[cfg(feature = "lzma")]
+            Some(PbfBlobData::LzmaData(ref data)) => {
+                let decoder = xz2::bufread::XzDecoder::new(&data[..]);
+                read_data(decoder, self.blob.raw_size())
+            }
+            _ => Err(Error::UnsupportedEncoding),
+        }
+    }
+
+    /// The (possibly compressed) content of this blob.
+    #[inline]
+    pub fn data(&self) -> &PbfBlob {
+        &self.blob
+    }
+
+    /// The name of the encoding of the data (`raw`, `zlib`, `lzma`, `bzip2`,
+    /// `lz4`, `zstd`, `unknown` or `none`), regardless of whether it is supported.
+    pub fn encoding(&self) -> &'static str {
+        match self.blob.data {
+            Some(PbfBlobData::Raw(_)) => "raw",
+            Some(PbfBlobData::ZlibData(_)) => "zlib",
+            Some(PbfBlobData::LzmaData(_)) => "lzma",
+            Some(PbfBlobData::OBSOLETEBzip2Data(_)) => "bzip2",
+            Some(PbfBlobData::Lz4Data(_)) => "lz4",
+            Some(PbfBlobData::ZstdData(_)) => "zstd",
+            Some(_) => "unknown",
+            None => "none",
         }
     }
 }
@@ -104,6 +244,16 @@ impl<R> Blobs<R> {
     pub fn into_inner(self) -> R {
         self.0
     }
+
+    #[inline]
+    pub fn get_ref(&self) -> &R {
+        &self.0
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.0
+    }
 }
 
 impl<R: AsRef<[u8]>> Blobs<io::Cursor<R>> {
@@ -136,7 +286,7 @@ impl<R: io::BufRead> Blobs<R> {
 
     pub fn header(&mut self) -> Result<OSMHeaderBlob> {
         match self.next_blob()? {
-            Some((header, blob)) if header.type_() == "OSMHeader" => {
+            Some((header, blob)) if header.type_() == HeaderBlock::BLOB_TYPE => {
                 Ok(OSMHeaderBlob::new(header, blob))
             }
             Some((header, _)) => Err(Error::UnexpectedBlobType(header.type_().to_string())),
@@ -152,7 +302,8 @@ impl<R: io::BufRead> Blobs<R> {
         Ok(msg)
     }
 
-    fn next_blob(&mut self) -> Result<Option<(PbfBlobHeader, PbfBlob)>> {
+    /// Reads the next `BlobHeader` and returns it together with its encoded size.
+    pub(crate) fn next_blob_header(&mut self) -> Result<Option<(u32, PbfBlobHeader)>> {
         let header_size = match self.0.read_u32::<BigEndian>() {
             Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
                 return Ok(None); // Expected EOF
@@ -161,16 +312,21 @@ impl<R: io::BufRead> Blobs<R> {
             Ok(header_size) if header_size > MAX_HEADER_SIZE => {
                 return Err(Error::BlobHeaderToLarge);
             }
-            Ok(header_size) => header_size as usize,
+            Ok(header_size) => header_size,
         };
 
-        let header: PbfBlobHeader = self.read_msg_exact(header_size)?;
-        let data_size = header.datasize() as usize;
-        if data_size > MAX_UNCOMPRESSED_DATA_SIZE {
+        let header: PbfBlobHeader = self.read_msg_exact(header_size as usize)?;
+        if header.datasize() as usize > MAX_UNCOMPRESSED_DATA_SIZE {
             return Err(Error::BlobDataToLarge);
         }
+        Ok(Some((header_size, header)))
+    }
 
-        let blob: PbfBlob = self.read_msg_exact(data_size)?;
+    pub(crate) fn next_blob(&mut self) -> Result<Option<(PbfBlobHeader, PbfBlob)>> {
+        let Some((_, header)) = self.next_blob_header()? else {
+            return Ok(None);
+        };
+        let blob: PbfBlob = self.read_msg_exact(header.datasize() as usize)?;
         Ok(Some((header, blob)))
     }
 }
@@ -187,7 +343,7 @@ impl<R: io::BufRead> iter::Iterator for Blobs<R> {
                 Ok(None) => {
                     return None;
                 }
-                Ok(Some((header, blob))) if header.type_() == "OSMData" => {
+                Ok(Some((header, blob))) if header.type_() == PrimitiveBlock::BLOB_TYPE => {
                     return Some(Ok(OSMDataBlob::new(header, blob)));
                 }
                 // skip unsupported blobs and header-blobs
@@ -198,3 +354,121 @@ impl<R: io::BufRead> iter::Iterator for Blobs<R> {
 }
 
 impl<R: io::BufRead> iter::FusedIterator for Blobs<R> {}
+
+/// Writes blobs in the `.osm.pbf` file-format.
+///
+/// This is the counterpart of [`Blobs`].
+#[derive(Debug)]
+pub struct BlobWriter<W> {
+    inner: W,
+    compression: Compression,
+}
+
+impl<W> BlobWriter<W> {
+    #[inline]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Sets the compression used by [`BlobWriter::write_block`].
+    #[inline]
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    #[inline]
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+}
+
+impl<W: io::Write> BlobWriter<W> {
+    #[inline]
+    pub fn new(write: W) -> Self {
+        Self {
+            inner: write,
+            compression: Compression::default(),
+        }
+    }
+
+    /// Writes the blob unchanged.
+    pub fn write_blob<M>(&mut self, blob: &Blob<M>) -> Result<()> {
+        self.write_raw_blob(&blob.header, &blob.blob)
+    }
+
+    /// Encodes the block with the configured compression and writes it.
+    #[inline]
+    pub fn write_block<M: Block>(&mut self, block: &M) -> Result<()> {
+        let blob = Blob::encode(block, self.compression)?;
+        self.write_blob(&blob)
+    }
+
+    fn write_raw_blob(&mut self, header: &PbfBlobHeader, blob: &PbfBlob) -> Result<()> {
+        let blob_data = blob.write_to_bytes()?;
+        if blob_data.len() > MAX_UNCOMPRESSED_DATA_SIZE {
+            return Err(Error::BlobDataToLarge);
+        }
+        let header_data = if header.datasize() as usize == blob_data.len() {
+            header.write_to_bytes()?
+        } else {
+            let mut header = header.clone();
+            header.set_datasize(blob_data.len() as i32);
+            header.write_to_bytes()?
+        };
+        if header_data.len() > MAX_HEADER_SIZE as usize {
+            return Err(Error::BlobHeaderToLarge);
+        }
+        self.inner
+            .write_u32::<BigEndian>(header_data.len() as u32)?;
+        self.inner.write_all(&header_data)?;
+        self.inner.write_all(&blob_data)?;
+        Ok(())
+    }
+
+    #[inline]
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{builder::PrimitiveBlockBuilder, node::NodeId, Meta};
+
+    /// The strings of a block with the tag `ab=cd`, decoded from a blob
+    /// with the given compression.
+    fn strings(compression: Compression) -> Vec<Bytes> {
+        let mut builder = PrimitiveBlockBuilder::new();
+        builder.add_node(NodeId(1), 0, 0, [("ab", "cd")], &Meta::default());
+        let blob = OSMDataBlob::encode(&builder.build(), compression).unwrap();
+        blob.decode().unwrap().strings().raw().to_vec()
+    }
+
+    /// Both strings are slices of the same decompressed buffer: the second
+    /// one starts right after the first one and its field tag and length.
+    fn assert_same_buffer(strings: &[Bytes]) {
+        let (ab, cd) = (&strings[1], &strings[2]);
+        assert_eq!((&ab[..], &cd[..]), (&b"ab"[..], &b"cd"[..]));
+        assert_eq!(ab.as_ptr().wrapping_add(ab.len() + 2), cd.as_ptr());
+    }
+
+    #[test]
+    fn decode_raw_without_copies() {
+        assert_same_buffer(&strings(Compression::None));
+    }
+
+    #[cfg(feature = "zlib")]
+    #[test]
+    fn decode_zlib_without_copies() {
+        assert_same_buffer(&strings(Compression::Zlib(6)));
+    }
+
+    #[cfg(feature = "lzma")]
+    #[test]
+    fn decode_lzma_without_copies() {
+        assert_same_buffer(&strings(Compression::Lzma(6)));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{btree_map, BTreeMap};
+use std::iter::{FusedIterator, Peekable};
+
+use crate::data::{
+    primitive::{OwnedPrimitive, OwnedPrimitives},
+    OSMDataBlob,
+};
+use crate::error::{Error, Result};
+
+/// The action of a change in an OsmChange (`.osc`) file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Action {
+    Create,
+    Modify,
+    Delete,
+}
+
+/// A created, modified or deleted element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Change {
+    pub action: Action,
+    pub element: OwnedPrimitive,
+}
+
+impl Change {
+    /// Returns `true` for deletions and elements with `visible=false`.
+    #[inline]
+    pub fn is_deletion(&self) -> bool {
+        self.action == Action::Delete || self.element.meta().is_some_and(|m| !m.visible)
+    }
+
+    #[inline]
+    fn version(&self) -> u32 {
+        self.element.meta().map_or(0, |m| m.version)
+    }
+}
+
+/// Sort key of `Sort.Type_then_ID`.
+#[inline]
+fn key(element: &OwnedPrimitive) -> (u32, i64) {
+    (element.primitive_type().bits(), element.id())
+}
+
+/// The latest change per element, collected from one or more diffs.
+#[derive(Clone, Debug, Default)]
+pub struct Changes {
+    changes: BTreeMap<(u32, i64), Change>,
+}
+
+impl Changes {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Adds a change. It replaces an earlier change of the same element,
+    /// unless that one has a higher version.
+    pub fn add(&mut self, change: Change) {
+        match self.changes.entry(key(&change.element)) {
+            btree_map::Entry::Vacant(entry) => {
+                entry.insert(change);
+            }
+            btree_map::Entry::Occupied(mut entry) => {
+                if change.version() >= entry.get().version() {
+                    entry.insert(change);
+                }
+            }
+        }
+    }
+
+    /// Merges the changes into a stream of elements sorted by type and id.
+    ///
+    /// Changes replace elements with the same or a lower version, deletions
+    /// remove them. The result is sorted by type and id as well; fails with
+    /// [`Error::NotSortedByTypeThenId`] when the input is not sorted.
+    pub fn apply<I>(self, base: I) -> ApplyChanges<I>
+    where
+        I: Iterator<Item = Result<OwnedPrimitive>>,
+    {
+        ApplyChanges {
+            base,
+            changes: self.changes.into_iter().peekable(),
+            next_base: None,
+            last_key: None,
+            done: false,
+        }
+    }
+
+    /// Merges the changes into the elements of sorted data blobs, like
+    /// [`Blobs`](crate::Blobs), see [`Changes::apply`].
+    #[inline]
+    pub fn apply_to_blobs<I>(self, blobs: I) -> ApplyChanges<OwnedPrimitives<I>>
+    where
+        I: Iterator<Item = Result<OSMDataBlob>>,
+    {
+        self.apply(OwnedPrimitives::new(blobs))
+    }
+}
+
+impl Extend<Change> for Changes {
+    fn extend<T: IntoIterator<Item = Change>>(&mut self, iter: T) {
+        for change in iter {
+            self.add(change);
+        }
+    }
+}
+
+impl FromIterator<Change> for Changes {
+    fn from_iter<T: IntoIterator<Item = Change>>(iter: T) -> Self {
+        let mut changes = Self::new();
+        changes.extend(iter);
+        changes
+    }
+}
+
+/// Iterator over the elements with the changes applied, see [`Changes::apply`].
+pub struct ApplyChanges<I> {
+    base: I,
+    changes: Peekable<btree_map::IntoIter<(u32, i64), Change>>,
+    next_base: Option<OwnedPrimitive>,
+    last_key: Option<(u32, i64)>,
+    done: bool,
+}
+
+impl<I: Iterator<Item = Result<OwnedPrimitive>>> ApplyChanges<I> {
+    fn fill_base(&mut self) -> Result<()> {
+        if self.next_base.is_some() {
+            return Ok(());
+        }
+        let Some(element) = self.base.next().transpose()? else {
+            return Ok(());
+        };
+        let key = key(&element);
+        if self.last_key.is_some_and(|last| last >= key) {
+            return Err(Error::NotSortedByTypeThenId);
+        }
+        self.last_key = Some(key);
+        self.next_base = Some(element);
+        Ok(())
+    }
+
+    fn next_element(&mut self) -> Result<Option<OwnedPrimitive>> {
+        loop {
+            self.fill_base()?;
+            let base_key = self.next_base.as_ref().map(key);
+            let change_key = self.changes.peek().map(|(k, _)| *k);
+            let (base, change) = match (base_key, change_key) {
+                (None, None) => return Ok(None),
+                (Some(_), None) => (self.next_base.take(), None),
+                (None, Some(_)) => (None, self.changes.next()),
+                (Some(b), Some(c)) if b < c => (self.next_base.take(), None),
+                (Some(b), Some(c)) if b > c => (None, self.changes.next()),
+                (Some(_), Some(_)) => (self.next_base.take(), self.changes.next()),
+            };
+            match (base, change) {
+                (Some(base), Some((_, change)))
+                    if change.version() < base.meta().map_or(0, |m| m.version) =>
+                {
+                    return Ok(Some(base))
+                }
+                (_, Some((_, change))) if change.is_deletion() => continue,
+                (_, Some((_, change))) => return Ok(Some(change.element)),
+                (base, None) => return Ok(base),
+            }
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<OwnedPrimitive>>> Iterator for ApplyChanges<I> {
+    type Item = Result<OwnedPrimitive>;
+
+    fn next(&mut self) -> Option<Result<OwnedPrimitive>> {
+        if self.done {
+            return None;
+        }
+        let result = self.next_element().transpose();
+        if !matches!(result, Some(Ok(_))) {
+            // don't continue after an error
+            self.done = true;
+        }
+        result
+    }
+}
+
+impl<I: Iterator<Item = Result<OwnedPrimitive>>> FusedIterator for ApplyChanges<I> {}
@@ -0,0 +1,569 @@
+use std::collections::HashMap;
+
+use osm_pbf_proto::{
+    osmformat::{
+        relation::MemberType as PbfMemberType, ChangeSet as PbfChangeSet,
+        DenseInfo as PbfDenseInfo, Info as PbfInfo, PrimitiveGroup as PbfPrimitiveGroup,
+        Relation as PbfRelation, Way as PbfWay,
+    },
+    protobuf::EnumOrUnknown,
+};
+
+use super::{
+    changeset::ChangeSetId,
+    node::NodeId,
+    primitive::{OwnedPrimitive, Primitive, PrimitiveType},
+    relation::{Member, OwnedMember, RelationId},
+    way::WayId,
+    DenseState, Meta, Offset, PrimitiveBlock,
+};
+
+/// Collects nodes, ways and relations and packs them into a [`PrimitiveBlock`].
+///
+/// Nodes are stored as `DenseNodes`, ids, coordinates and refs are delta-coded
+/// and all strings are collected in a shared string table. Consecutive
+/// elements of the same type are placed into the same primitive group.
+#[derive(Clone)]
+pub struct PrimitiveBlockBuilder {
+    strings: Vec<String>,
+    string_indices: HashMap<String, u32>,
+    primitive_groups: Vec<PbfPrimitiveGroup>,
+    group_type: PrimitiveType,
+    offset: Offset,
+    dense_state: DenseState,
+    len: usize,
+}
+
+impl Default for PrimitiveBlockBuilder {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PrimitiveBlockBuilder {
+    pub fn new() -> Self {
+        Self {
+            // index 0 is reserved as delimiter in `DenseNodes`
+            strings: vec![String::new()],
+            string_indices: HashMap::new(),
+            primitive_groups: Vec::new(),
+            group_type: PrimitiveType::empty(),
+            offset: Offset::default(),
+            dense_state: DenseState::default(),
+            len: 0,
+        }
+    }
+
+    /// Sets the granularity of the coordinates in nanodegrees (default: `100`).
+    ///
+    /// Must be called before any node has been added.
+    #[inline]
+    pub fn with_granularity(mut self, granularity: i32) -> Self {
+        debug_assert!(self.is_empty());
+        self.offset.granularity = granularity;
+        self
+    }
+
+    /// Sets the granularity of the timestamps in milliseconds (default: `1000`).
+    ///
+    /// Must be called before any element has been added.
+    #[inline]
+    pub fn with_date_granularity(mut self, date_granularity: i32) -> Self {
+        debug_assert!(self.is_empty());
+        self.offset.date_granularity = date_granularity;
+        self
+    }
+
+    /// Sets the offset of the coordinates in nanodegrees (default: `0`).
+    ///
+    /// Must be called before any node has been added.
+    #[inline]
+    pub fn with_offset(mut self, nano_lat_offset: i64, nano_lon_offset: i64) -> Self {
+        debug_assert!(self.is_empty());
+        self.offset.lat = nano_lat_offset;
+        self.offset.lon = nano_lon_offset;
+        self
+    }
+
+    /// Number of elements added to this block.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn string_index(&mut self, s: &str) -> u32 {
+        if s.is_empty() {
+            return 0;
+        }
+        if let Some(&index) = self.string_indices.get(s) {
+            return index;
+        }
+        let index = self.strings.len() as u32;
+        self.strings.push(s.to_string());
+        self.string_indices.insert(s.to_string(), index);
+        index
+    }
+
+    fn group(&mut self, group_type: PrimitiveType) -> &mut PbfPrimitiveGroup {
+        if self.group_type != group_type || self.primitive_groups.is_empty() {
+            self.finish_group();
+            self.group_type = group_type;
+            self.primitive_groups.push(PbfPrimitiveGroup::new());
+        }
+        self.primitive_groups.last_mut().unwrap()
+    }
+
+    fn finish_group(&mut self) {
+        self.dense_state = DenseState::default();
+        let Some(group) = self.primitive_groups.last_mut() else {
+            return;
+        };
+        // drop the dense info when it contains no information
+        if let Some(dense) = group.dense.as_mut() {
+            if let Some(info) = dense.denseinfo.as_mut() {
+                if info.visible.iter().all(|v| *v) {
+                    info.visible.clear();
+                }
+                if info.visible.is_empty()
+                    && info.version.iter().all(|v| *v == 0)
+                    && info.timestamp.iter().all(|v| *v == 0)
+                    && info.changeset.iter().all(|v| *v == 0)
+                    && info.uid.iter().all(|v| *v == 0)
+                    && info.user_sid.iter().all(|v| *v == 0)
+                {
+                    dense.denseinfo.clear();
+                }
+            }
+        }
+    }
+
+    fn info(&mut self, meta: &Meta<'_>) -> Option<PbfInfo> {
+        if *meta == Meta::default() {
+            return None;
+        }
+        let mut info = PbfInfo::new();
+        if meta.version != 0 {
+            info.set_version(meta.version as i32);
+        }
+        if meta.timestamp != 0 {
+            info.set_timestamp(meta.timestamp / self.offset.date_granularity as i64);
+        }
+        if meta.changeset.0 != 0 {
+            info.set_changeset(meta.changeset.0);
+        }
+        if meta.uid != 0 {
+            info.set_uid(meta.uid);
+        }
+        if !meta.user.is_empty() {
+            info.set_user_sid(self.string_index(meta.user));
+        }
+        if !meta.visible {
+            info.set_visible(false);
+        }
+        Some(info)
+    }
+
+    fn tag_indices<K, V>(&mut self, tags: impl IntoIterator<Item = (K, V)>) -> Vec<(u32, u32)>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        tags.into_iter()
+            .map(|(k, v)| (self.string_index(k.as_ref()), self.string_index(v.as_ref())))
+            .collect()
+    }
+
+    /// Adds a node with the given coordinates in nanodegrees.
+    pub fn add_node<K, V>(
+        &mut self,
+        id: NodeId,
+        nano_lat: i64,
+        nano_lon: i64,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let tags = self.tag_indices(tags);
+        let user_sid = self.string_index(meta.user) as i32;
+        let offset = self.offset;
+        let timestamp = meta.timestamp / offset.date_granularity as i64;
+        let lat = (nano_lat - offset.lat) / offset.granularity as i64;
+        let lon = (nano_lon - offset.lon) / offset.granularity as i64;
+        let mut state = self.dense_state;
+        let dense = self
+            .group(PrimitiveType::NODE)
+            .dense
+            .mut_or_insert_default();
+        dense.id.push(id.0 - state.id);
+        dense.lat.push(lat - state.lat);
+        dense.lon.push(lon - state.lon);
+        for (k, v) in tags {
+            dense.keys_vals.push(k as i32);
+            dense.keys_vals.push(v as i32);
+        }
+        dense.keys_vals.push(0);
+        let info: &mut PbfDenseInfo = dense.denseinfo.mut_or_insert_default();
+        info.version.push(meta.version as i32);
+        info.timestamp.push(timestamp - state.timestamp);
+        info.changeset.push(meta.changeset.0 - state.changeset);
+        info.uid.push(meta.uid - state.uid);
+        info.user_sid.push(user_sid - state.user_sid);
+        info.visible.push(meta.visible);
+        state.id = id.0;
+        state.lat = lat;
+        state.lon = lon;
+        state.timestamp = timestamp;
+        state.changeset = meta.changeset.0;
+        state.uid = meta.uid;
+        state.user_sid = user_sid;
+        self.dense_state = state;
+        self.len += 1;
+    }
+
+    /// Adds a way referencing the given nodes.
+    pub fn add_way<K, V>(
+        &mut self,
+        id: WayId,
+        refs: impl IntoIterator<Item = NodeId>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut way = self.new_way(id, tags, meta);
+        way.refs = delta_encode(refs.into_iter().map(|r| r.0));
+        self.group(PrimitiveType::WAY).ways.push(way);
+        self.len += 1;
+    }
+
+    /// Adds a way referencing the given nodes together with their locations
+    /// (`LocationsOnWays`) as `(nano_lat, nano_lon)`.
+    pub fn add_way_with_locations<K, V>(
+        &mut self,
+        id: WayId,
+        refs_with_locations: impl IntoIterator<Item = (NodeId, (i64, i64))>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut way = self.new_way(id, tags, meta);
+        let offset = self.offset;
+        let granularity = offset.granularity as i64;
+        let (refs, locations): (Vec<i64>, Vec<(i64, i64)>) = refs_with_locations
+            .into_iter()
+            .map(|(r, (lat, lon))| {
+                let lat = (lat - offset.lat) / granularity;
+                let lon = (lon - offset.lon) / granularity;
+                (r.0, (lat, lon))
+            })
+            .unzip();
+        let (lat, lon): (Vec<i64>, Vec<i64>) = locations.into_iter().unzip();
+        way.refs = delta_encode(refs);
+        way.lat = delta_encode(lat);
+        way.lon = delta_encode(lon);
+        self.group(PrimitiveType::WAY).ways.push(way);
+        self.len += 1;
+    }
+
+    fn new_way<K, V>(
+        &mut self,
+        id: WayId,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> PbfWay
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut way = PbfWay::new();
+        way.set_id(id.0);
+        (way.keys, way.vals) = self.tag_indices(tags).into_iter().unzip();
+        way.info = self.info(meta).into();
+        way
+    }
+
+    /// Adds a relation with the given members.
+    pub fn add_relation<'m, K, V>(
+        &mut self,
+        id: RelationId,
+        members: impl IntoIterator<Item = Member<'m>>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let mut relation = PbfRelation::new();
+        relation.set_id(id.0);
+        (relation.keys, relation.vals) = self.tag_indices(tags).into_iter().unzip();
+        relation.info = self.info(meta).into();
+        let mut last = 0;
+        for member in members {
+            let (member_type, member_id, role) = match member {
+                Member::Node(NodeId(id), role) => (PbfMemberType::NODE, id, role),
+                Member::Way(WayId(id), role) => (PbfMemberType::WAY, id, role),
+                Member::Relation(RelationId(id), role) => (PbfMemberType::RELATION, id, role),
+            };
+            relation.roles_sid.push(self.string_index(role) as i32);
+            relation.memids.push(member_id - last);
+            relation.types.push(EnumOrUnknown::new(member_type));
+            last = member_id;
+        }
+        self.group(PrimitiveType::RELATION).relations.push(relation);
+        self.len += 1;
+    }
+
+    /// Adds a changeset.
+    pub fn add_changeset(&mut self, id: ChangeSetId) {
+        let mut changeset = PbfChangeSet::new();
+        changeset.set_id(id.0);
+        self.group(PrimitiveType::CHANGE_SET)
+            .changesets
+            .push(changeset);
+        self.len += 1;
+    }
+
+    /// Adds a copy of the given primitive.
+    pub fn add_primitive(&mut self, primitive: &Primitive<'_>) {
+        match primitive {
+            Primitive::Node(n) => self.add_node(n.id, n.nano_lat, n.nano_lon, n.tags(), n),
+            Primitive::Way(w) if w.has_locations() => {
+                self.add_way_with_locations(w.id, w.refs_with_locations(), w.tags(), w)
+            }
+            Primitive::Way(w) => self.add_way(w.id, w.refs(), w.tags(), w),
+            Primitive::Relation(r) => self.add_relation(r.id, r.members(), r.tags(), r),
+            Primitive::ChangeSet(c) => self.add_changeset(c.id),
+        }
+    }
+
+    /// Adds a copy of the given owned primitive.
+    pub fn add_owned(&mut self, primitive: &OwnedPrimitive) {
+        match primitive {
+            OwnedPrimitive::Node(n) => self.add_node(
+                n.id,
+                n.nano_lat,
+                n.nano_lon,
+                n.tags.iter().map(|(k, v)| (k, v)),
+                &n.meta.as_meta(),
+            ),
+            OwnedPrimitive::Way(w) if !w.locations.is_empty() => self.add_way_with_locations(
+                w.id,
+                w.refs.iter().copied().zip(w.locations.iter().copied()),
+                w.tags.iter().map(|(k, v)| (k, v)),
+                &w.meta.as_meta(),
+            ),
+            OwnedPrimitive::Way(w) => self.add_way(
+                w.id,
+                w.refs.iter().copied(),
+                w.tags.iter().map(|(k, v)| (k, v)),
+                &w.meta.as_meta(),
+            ),
+            OwnedPrimitive::Relation(r) => self.add_relation(
+                r.id,
+                r.members.iter().map(OwnedMember::as_member),
+                r.tags.iter().map(|(k, v)| (k, v)),
+                &r.meta.as_meta(),
+            ),
+            OwnedPrimitive::ChangeSet(c) => self.add_changeset(c.id),
+        }
+    }
+
+    /// Finishes the block and resets this builder.
+    pub fn build(&mut self) -> PrimitiveBlock {
+        self.finish_group();
+        let offset = self.offset;
+        let builder = std::mem::take(self);
+        self.offset = offset;
+        PrimitiveBlock {
+            strings: builder.strings.into(),
+            primitive_groups: builder.primitive_groups,
+            offset,
+        }
+    }
+}
+
+fn delta_encode(values: impl IntoIterator<Item = i64>) -> Vec<i64> {
+    let mut last = 0;
+    values
+        .into_iter()
+        .map(|value| {
+            let delta = value - last;
+            last = value;
+            delta
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::Block;
+
+    /// Encodes and decodes the block like a blob does.
+    fn round_trip(block: &PrimitiveBlock) -> PrimitiveBlock {
+        PrimitiveBlock::from_message(block.to_message().unwrap()).unwrap()
+    }
+
+    type Tags = Vec<(String, String)>;
+
+    fn nodes(block: &PrimitiveBlock) -> Vec<(i64, i64, i64, Tags)> {
+        block
+            .primitives()
+            .map(|p| match p.to_owned() {
+                OwnedPrimitive::Node(n) => (n.id.0, n.nano_lat, n.nano_lon, n.tags),
+                p => panic!("unexpected {p:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dense_nodes_delta_coding() {
+        let mut builder = PrimitiveBlockBuilder::new();
+        let meta = Meta {
+            version: 2,
+            timestamp: 1_356_373_800_000,
+            changeset: ChangeSetId(30),
+            uid: 7,
+            user: "foo",
+            visible: true,
+        };
+        builder.add_node(NodeId(5), 100, -200, [("a", "b")], &meta);
+        builder.add_node(NodeId(3), 400, -200, [("a", "c"), ("d", "b")], &meta);
+        let tags: [(&str, &str); 0] = [];
+        builder.add_node(
+            NodeId(10),
+            -300,
+            500,
+            tags,
+            &Meta {
+                changeset: ChangeSetId(25),
+                ..meta
+            },
+        );
+        let block = builder.build();
+
+        let dense = block.primitive_groups[0].dense.as_ref().unwrap();
+        assert_eq!(dense.id, [5, -2, 7]);
+        assert_eq!(dense.lat, [1, 3, -7]);
+        assert_eq!(dense.lon, [-2, 0, 7]);
+        let [a, b, c, d] = ["a", "b", "c", "d"].map(|s| string_index(&block, s));
+        assert_eq!(dense.keys_vals, [a, b, 0, a, c, d, b, 0, 0]);
+        let info = dense.denseinfo.as_ref().unwrap();
+        assert_eq!(info.timestamp, [1_356_373_800, 0, 0]);
+        assert_eq!(info.changeset, [30, 0, -5]);
+        assert_eq!(info.uid, [7, 0, 0]);
+        assert!(info.visible.is_empty());
+
+        let block = round_trip(&block);
+        assert_eq!(
+            nodes(&block),
+            [
+                (5, 100, -200, vec![("a".into(), "b".into())]),
+                (
+                    3,
+                    400,
+                    -200,
+                    vec![("a".into(), "c".into()), ("d".into(), "b".into())]
+                ),
+                (10, -300, 500, vec![]),
+            ]
+        );
+        let metas: Vec<_> = block
+            .primitives()
+            .map(|p| p.to_owned().meta().unwrap().changeset.0)
+            .collect();
+        assert_eq!(metas, [30, 30, 25]);
+    }
+
+    fn string_index(block: &PrimitiveBlock, s: &str) -> i32 {
+        block
+            .strings
+            .raw()
+            .iter()
+            .position(|b| b == s.as_bytes())
+            .unwrap() as i32
+    }
+
+    #[test]
+    fn drop_empty_dense_info() {
+        let tags: [(&str, &str); 0] = [];
+        let mut builder = PrimitiveBlockBuilder::new();
+        builder.add_node(NodeId(1), 0, 0, tags, &Meta::default());
+        builder.add_node(NodeId(2), 0, 0, tags, &Meta::default());
+        let block = builder.build();
+        let dense = block.primitive_groups[0].dense.as_ref().unwrap();
+        assert!(dense.denseinfo.is_none());
+        let block = round_trip(&block);
+        assert!(block.primitives().all(|p| p
+            .to_owned()
+            .meta()
+            .is_some_and(|m| m.visible && m.version == 0)));
+
+        // a deleted node keeps the info, including the visible flags
+        let deleted = Meta {
+            visible: false,
+            ..Meta::default()
+        };
+        builder.add_node(NodeId(1), 0, 0, tags, &Meta::default());
+        builder.add_node(NodeId(2), 0, 0, tags, &deleted);
+        let block = builder.build();
+        let info = block.primitive_groups[0]
+            .dense
+            .as_ref()
+            .unwrap()
+            .denseinfo
+            .as_ref()
+            .unwrap();
+        assert_eq!(info.visible, [true, false]);
+        let visible: Vec<_> = round_trip(&block)
+            .primitives()
+            .map(|p| p.to_owned().meta().unwrap().visible)
+            .collect();
+        assert_eq!(visible, [true, false]);
+    }
+
+    #[test]
+    fn keep_offset_across_build() {
+        let tags: [(&str, &str); 0] = [];
+        let mut builder = PrimitiveBlockBuilder::new()
+            .with_granularity(1000)
+            .with_date_granularity(60_000)
+            .with_offset(52_000_000_000, 13_000_000_000);
+        let meta = Meta {
+            timestamp: 1_356_373_800_000,
+            ..Meta::default()
+        };
+        for id in 1..3 {
+            builder.add_node(NodeId(id), 52_000_001_000 * id, 13_000_002_000, tags, &meta);
+            let block = builder.build();
+            assert!(builder.is_empty());
+            assert_eq!(block.offset.granularity, 1000);
+            assert_eq!(block.offset.date_granularity, 60_000);
+            assert_eq!(
+                (block.offset.lat, block.offset.lon),
+                (52_000_000_000, 13_000_000_000)
+            );
+            let dense = block.primitive_groups[0].dense.as_ref().unwrap();
+            assert_eq!(dense.id, [id]);
+
+            let block = round_trip(&block);
+            assert_eq!(
+                nodes(&block),
+                [(id, 52_000_001_000 * id, 13_000_002_000, vec![])]
+            );
+            let timestamp = block.primitives().next().unwrap().to_owned();
+            assert_eq!(timestamp.meta().unwrap().timestamp, 1_356_373_800_000);
+        }
+    }
+}
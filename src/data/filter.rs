@@ -0,0 +1,315 @@
+use std::iter;
+use std::str::FromStr;
+
+use super::packed::Packed;
+use super::string_table::StringTable;
+use crate::error::{Error, Result};
+
+/// A condition on the tags of an element.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub enum TagCondition {
+    /// `key` or `key=*`: the key is present.
+    Exists(String),
+    /// `!key`: the key is not present.
+    NotExists(String),
+    /// `key=value`
+    Equals(String, String),
+    /// `key!=value`: the key is present with another value.
+    NotEquals(String, String),
+    /// `key~regex`: the key is present with a value matching the expression.
+    #[cfg(feature = "regex")]
+    Matches(String, regex::Regex),
+}
+
+impl TagCondition {
+    #[inline]
+    fn key(&self) -> &str {
+        match self {
+            TagCondition::Exists(k)
+            | TagCondition::NotExists(k)
+            | TagCondition::Equals(k, _)
+            | TagCondition::NotEquals(k, _) => k,
+            #[cfg(feature = "regex")]
+            TagCondition::Matches(k, _) => k,
+        }
+    }
+
+    /// Tests the value of a tag with the key of this condition.
+    #[inline]
+    fn accepts(&self, value: &str) -> bool {
+        match self {
+            TagCondition::Exists(_) | TagCondition::NotExists(_) => true,
+            TagCondition::Equals(_, v) => value == v,
+            TagCondition::NotEquals(_, v) => value != v,
+            #[cfg(feature = "regex")]
+            TagCondition::Matches(_, r) => r.is_match(value),
+        }
+    }
+
+    #[inline]
+    fn is_negated(&self) -> bool {
+        matches!(self, TagCondition::NotExists(_))
+    }
+}
+
+impl FromStr for TagCondition {
+    type Err = Error;
+
+    /// Parses `key`, `key=*`, `!key`, `key=value`, `key!=value` or `key~regex`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidTagFilter(s.to_string());
+        // split at the first operator, the value may contain the others
+        let condition = match s.find(['=', '~']) {
+            Some(i) if s[i..].starts_with('=') && s[..i].ends_with('!') => {
+                TagCondition::NotEquals(s[..i - 1].to_string(), s[i + 1..].to_string())
+            }
+            Some(i) if s[i..].starts_with('=') => {
+                let (k, v) = (&s[..i], &s[i + 1..]);
+                if v == "*" {
+                    TagCondition::Exists(k.to_string())
+                } else {
+                    TagCondition::Equals(k.to_string(), v.to_string())
+                }
+            }
+            Some(_i) => {
+                #[cfg(feature = "regex")]
+                {
+                    let regex = regex::Regex::new(&s[_i + 1..]).map_err(|_| invalid())?;
+                    TagCondition::Matches(s[.._i].to_string(), regex)
+                }
+                #[cfg(not(feature = "regex"))]
+                return Err(invalid());
+            }
+            None => match s.strip_prefix('!') {
+                Some(k) => TagCondition::NotExists(k.to_string()),
+                None => TagCondition::Exists(s.to_string()),
+            },
+        };
+        if condition.key().is_empty() {
+            return Err(invalid());
+        }
+        Ok(condition)
+    }
+}
+
+/// A combination of [`TagCondition`]s, see [`Primitives::filter_tags`](super::primitive::Primitives::filter_tags).
+#[derive(Clone, Debug)]
+pub struct TagFilter {
+    conditions: Vec<TagCondition>,
+    any: bool,
+}
+
+impl TagFilter {
+    /// Matches elements that satisfy all of the conditions.
+    pub fn all(conditions: impl IntoIterator<Item = TagCondition>) -> Self {
+        Self {
+            conditions: conditions.into_iter().collect(),
+            any: false,
+        }
+    }
+
+    /// Matches elements that satisfy at least one of the conditions.
+    pub fn any(conditions: impl IntoIterator<Item = TagCondition>) -> Self {
+        Self {
+            conditions: conditions.into_iter().collect(),
+            any: true,
+        }
+    }
+
+    /// Tests the tags of an element, e.g. of an owned element.
+    pub fn matches<K, V>(&self, tags: impl IntoIterator<Item = (K, V)> + Clone) -> bool
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let test = |c: &TagCondition| {
+            let found = tags
+                .clone()
+                .into_iter()
+                .any(|(k, v)| k.as_ref() == c.key() && c.accepts(v.as_ref()));
+            found != c.is_negated()
+        };
+        if self.any {
+            self.conditions.iter().any(test)
+        } else {
+            self.conditions.iter().all(test)
+        }
+    }
+
+    /// Resolves the conditions to indices of the string table of a block.
+    pub(crate) fn compile(&self, strings: &StringTable) -> CompiledTagFilter {
+        let conditions: Vec<CompiledCondition> = self
+            .conditions
+            .iter()
+            .map(|c| {
+                let keys: Vec<u32> = (0..strings.len() as u32)
+                    .filter(|&i| strings.get(i as usize) == Some(c.key()))
+                    .collect();
+                let values = match c {
+                    TagCondition::Exists(_) | TagCondition::NotExists(_) => None,
+                    _ if keys.is_empty() => None,
+                    _ => Some(strings.iter().map(|s| c.accepts(s)).collect()),
+                };
+                CompiledCondition {
+                    keys,
+                    values,
+                    negated: c.is_negated(),
+                }
+            })
+            .collect();
+        // a condition that can't be satisfied in this block
+        let unsatisfiable = |c: &CompiledCondition| c.keys.is_empty() && !c.negated;
+        let never = if self.any {
+            conditions.iter().all(unsatisfiable)
+        } else {
+            conditions.iter().any(unsatisfiable)
+        };
+        CompiledTagFilter {
+            conditions,
+            any: self.any,
+            never,
+        }
+    }
+}
+
+impl FromStr for TagFilter {
+    type Err = Error;
+
+    /// Parses a single [`TagCondition`].
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self::all([s.parse()?]))
+    }
+}
+
+struct CompiledCondition {
+    /// indices of the key in the string table
+    keys: Vec<u32>,
+    /// accepted values by index in the string table, `None` for any value
+    values: Option<Vec<bool>>,
+    negated: bool,
+}
+
+impl CompiledCondition {
+    #[inline]
+    fn test(&self, mut tags: impl Iterator<Item = (u32, u32)>) -> bool {
+        let found = tags.any(|(k, v)| {
+            self.keys.contains(&k)
+                && self
+                    .values
+                    .as_ref()
+                    .is_none_or(|values| values.get(v as usize).copied().unwrap_or(false))
+        });
+        found != self.negated
+    }
+}
+
+/// A [`TagFilter`] resolved to the string table of a block.
+pub(crate) struct CompiledTagFilter {
+    conditions: Vec<CompiledCondition>,
+    any: bool,
+    /// no element of the block can match
+    never: bool,
+}
+
+impl CompiledTagFilter {
+    #[inline]
+    pub fn never_matches(&self) -> bool {
+        self.never
+    }
+
+    /// Tests the tags given as pairs of string table indices.
+    pub fn matches(&self, tags: impl Iterator<Item = (u32, u32)> + Clone) -> bool {
+        if self.never {
+            return false;
+        }
+        if self.any {
+            self.conditions.iter().any(|c| c.test(tags.clone()))
+        } else {
+            self.conditions.iter().all(|c| c.test(tags.clone()))
+        }
+    }
+
+    #[inline]
+    pub fn matches_fields(&self, keys: Packed<'_, u32>, values: Packed<'_, u32>) -> bool {
+        self.matches(keys.iter().zip(values.iter()))
+    }
+
+    /// Tests the interleaved keys and values of a dense node.
+    #[inline]
+    pub fn matches_dense(&self, key_values: Packed<'_, i32>) -> bool {
+        let mut iter = key_values.iter();
+        self.matches(iter::from_fn(move || {
+            Some((iter.next()? as u32, iter.next()? as u32))
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> TagCondition {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_operators() {
+        assert!(matches!(parse("name"), TagCondition::Exists(k) if k == "name"));
+        assert!(matches!(parse("name=*"), TagCondition::Exists(k) if k == "name"));
+        assert!(matches!(parse("!name"), TagCondition::NotExists(k) if k == "name"));
+        assert!(
+            matches!(parse("highway=primary"), TagCondition::Equals(k, v) if k == "highway" && v == "primary")
+        );
+        assert!(
+            matches!(parse("building!=no"), TagCondition::NotEquals(k, v) if k == "building" && v == "no")
+        );
+        assert!(matches!(parse("a=b=c"), TagCondition::Equals(k, v) if k == "a" && v == "b=c"));
+        assert!(matches!(parse("a=b!=c"), TagCondition::Equals(k, v) if k == "a" && v == "b!=c"));
+        assert!(matches!(parse("a!=b~c"), TagCondition::NotEquals(k, v) if k == "a" && v == "b~c"));
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in ["", "=x", "!=x", "!", "~x"] {
+            assert!(s.parse::<TagCondition>().is_err(), "{s:?}");
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn parse_regex() {
+        let TagCondition::Matches(k, r) = parse("name~^a=b$") else {
+            panic!("not a regex");
+        };
+        assert_eq!((k.as_str(), r.as_str()), ("name", "^a=b$"));
+        let TagCondition::Matches(k, r) = parse("ref~x!=y") else {
+            panic!("not a regex");
+        };
+        assert_eq!((k.as_str(), r.as_str()), ("ref", "x!=y"));
+        assert!("name~(".parse::<TagCondition>().is_err());
+    }
+
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn parse_regex_unsupported() {
+        assert!("name~^a".parse::<TagCondition>().is_err());
+    }
+
+    #[test]
+    fn matches_tags() {
+        let tags = [("highway", "primary"), ("name", "a=b")];
+        let filter = |s: &str| TagFilter::from_str(s).unwrap();
+        assert!(filter("highway").matches(tags));
+        assert!(!filter("!highway").matches(tags));
+        assert!(filter("highway=primary").matches(tags));
+        assert!(!filter("highway!=primary").matches(tags));
+        assert!(filter("name=a=b").matches(tags));
+        assert!(!filter("building").matches(tags));
+        assert!(filter("building!=no").matches([("building", "yes")]));
+        let any = TagFilter::any([parse("building"), parse("highway=primary")]);
+        assert!(any.matches(tags));
+        let all = TagFilter::all([parse("building"), parse("highway=primary")]);
+        assert!(!all.matches(tags));
+    }
+}
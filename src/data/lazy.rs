@@ -0,0 +1,876 @@
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::slice;
+
+use bytes::Bytes;
+
+use super::{
+    changeset::{ChangeSet, ChangeSetId},
+    filter::{CompiledTagFilter, TagFilter},
+    node::Node,
+    packed::{read_varint, Packed, PackedIter, Varint},
+    primitive::{Primitive, PrimitiveType},
+    relation::Relation,
+    string_table::StringTable,
+    tags::{NodeTagFields, TagFields},
+    way::Way,
+    DenseState, Meta, OSMDataBlob, Offset,
+};
+use crate::error::{Error, Result};
+
+/// A [`PrimitiveBlock`](super::PrimitiveBlock) that is decoded lazily from
+/// the decompressed buffer of its blob.
+///
+/// Only the string table and the positions of the primitive groups are read
+/// when the block is parsed; no message tree is built. The elements are
+/// parsed while iterating over them, skipped element types are never looked
+/// at, and the packed fields (e.g. of dense nodes) are decoded on the fly.
+///
+/// Repeated fields must be packed, as they are in all files written
+/// according to the format description.
+#[derive(Clone)]
+pub struct LazyBlock {
+    strings: StringTable,
+    groups: Vec<Bytes>,
+    offset: Offset,
+}
+
+impl LazyBlock {
+    /// Parses the block from the decompressed data, see [`Blob::decompress`](crate::Blob::decompress).
+    #[inline]
+    pub fn parse(data: Bytes) -> Result<Self> {
+        Self::parse_with(data, StringTable::new)
+    }
+
+    /// Like [`LazyBlock::parse`], but replaces invalid UTF-8 in the string
+    /// table with `U+FFFD` instead of failing.
+    #[inline]
+    pub fn parse_lossy(data: Bytes) -> Result<Self> {
+        Self::parse_with(data, |strings| Ok(StringTable::new_lossy(strings)))
+    }
+
+    fn parse_with(
+        data: Bytes,
+        string_table: impl FnOnce(Vec<Bytes>) -> Result<StringTable>,
+    ) -> Result<Self> {
+        let mut strings = Vec::new();
+        let mut groups = Vec::new();
+        let mut offset = Offset::default();
+        for field in Fields(&data) {
+            match field? {
+                (1, value) => {
+                    for field in Fields(value.bytes()?) {
+                        if let (1, s) = field? {
+                            strings.push(data.slice_ref(s.bytes()?));
+                        }
+                    }
+                }
+                (2, value) => groups.push(data.slice_ref(value.bytes()?)),
+                (17, value) => offset.granularity = value.varint()? as i32,
+                (18, value) => offset.date_granularity = value.varint()? as i32,
+                (19, value) => offset.lat = value.varint()? as i64,
+                (20, value) => offset.lon = value.varint()? as i64,
+                _ => {}
+            }
+        }
+        Ok(Self {
+            strings: string_table(strings)?,
+            groups,
+            offset,
+        })
+    }
+
+    #[inline]
+    pub fn strings(&self) -> &StringTable {
+        &self.strings
+    }
+
+    /// The number of primitive groups.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.groups.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.groups.is_empty()
+    }
+
+    pub fn primitives(&self) -> LazyPrimitives<'_> {
+        LazyPrimitives {
+            strings: &self.strings,
+            groups: self.groups.iter(),
+            group: Fields(&[]),
+            dense: None,
+            filter: PrimitiveType::DEFAULT,
+            offset: self.offset,
+            tag_filter: None,
+        }
+    }
+}
+
+impl OSMDataBlob {
+    /// Decompresses the blob and parses it into a [`LazyBlock`].
+    pub fn decode_lazy(&self) -> Result<LazyBlock> {
+        LazyBlock::parse(self.decompress()?)
+    }
+}
+
+/// Iterator over the elements of a [`LazyBlock`].
+///
+/// Yields an error (and stops) when an element is malformed.
+pub struct LazyPrimitives<'l> {
+    strings: &'l StringTable,
+    groups: slice::Iter<'l, Bytes>,
+    /// the remaining fields of the current group
+    group: Fields<'l>,
+    dense: Option<DenseNodes<'l>>,
+    filter: PrimitiveType,
+    offset: Offset,
+    tag_filter: Option<CompiledTagFilter>,
+}
+
+impl<'l> LazyPrimitives<'l> {
+    #[inline]
+    pub fn types(mut self, types: PrimitiveType) -> Self {
+        self.filter = types;
+        self
+    }
+
+    /// Only yields elements with tags matching the filter, see
+    /// [`Primitives::filter_tags`](super::primitive::Primitives::filter_tags).
+    ///
+    /// The tags are tested before the rest of the element is parsed.
+    #[inline]
+    pub fn filter_tags(mut self, filter: &TagFilter) -> Self {
+        self.tag_filter = Some(filter.compile(self.strings));
+        self
+    }
+
+    #[inline]
+    fn accepts(&self, keys: Packed<'_, u32>, values: Packed<'_, u32>) -> bool {
+        self.tag_filter
+            .as_ref()
+            .is_none_or(|f| f.matches_fields(keys, values))
+    }
+
+    /// Parses a field of a primitive group; `None` when it is skipped.
+    fn element(&mut self, number: u32, value: Value<'l>) -> Result<Option<Primitive<'l>>> {
+        let strings = self.strings;
+        let offset = &self.offset;
+        Ok(Some(match number {
+            1 if self.filter.contains(PrimitiveType::NODE) => {
+                let e = Element::parse(value.bytes()?)?;
+                if !self.accepts(e.keys, e.vals) {
+                    return Ok(None);
+                }
+                Primitive::Node(Node::from_parts(
+                    i64::from_varint(e.id),
+                    e.scalar(0).map(i64::from_varint)?,
+                    e.scalar(1).map(i64::from_varint)?,
+                    offset,
+                    NodeTagFields::Normal(e.keys, e.vals),
+                    parse_meta(e.info, offset, strings)?,
+                    strings,
+                ))
+            }
+            2 if self.filter.contains(PrimitiveType::NODE) => {
+                self.dense = Some(DenseNodes::parse(value.bytes()?)?);
+                return Ok(None);
+            }
+            3 if self.filter.contains(PrimitiveType::WAY) => {
+                let e = Element::parse(value.bytes()?)?;
+                if !self.accepts(e.keys, e.vals) {
+                    return Ok(None);
+                }
+                Primitive::Way(Way::from_parts(
+                    e.id as i64,
+                    [e.packed(0)?, e.packed(1)?, e.packed(2)?],
+                    offset,
+                    TagFields(e.keys, e.vals),
+                    parse_meta(e.info, offset, strings)?,
+                    strings,
+                ))
+            }
+            4 if self.filter.contains(PrimitiveType::RELATION) => {
+                let e = Element::parse(value.bytes()?)?;
+                if !self.accepts(e.keys, e.vals) {
+                    return Ok(None);
+                }
+                Primitive::Relation(Relation::from_parts(
+                    e.id as i64,
+                    (e.packed(0)?, e.packed(1)?, e.packed(2)?),
+                    TagFields(e.keys, e.vals),
+                    parse_meta(e.info, offset, strings)?,
+                    strings,
+                ))
+            }
+            5 if self.filter.contains(PrimitiveType::CHANGE_SET) => {
+                // changesets have no tags
+                if !self.accepts(Packed::default(), Packed::default()) {
+                    return Ok(None);
+                }
+                let mut id = 0;
+                for field in Fields(value.bytes()?) {
+                    if let (1, value) = field? {
+                        id = value.varint()? as i64;
+                    }
+                }
+                Primitive::ChangeSet(ChangeSet {
+                    id: ChangeSetId(id),
+                })
+            }
+            _ => return Ok(None),
+        }))
+    }
+}
+
+impl<'l> Iterator for LazyPrimitives<'l> {
+    type Item = Result<Primitive<'l>>;
+
+    fn next(&mut self) -> Option<Result<Primitive<'l>>> {
+        if self.tag_filter.as_ref().is_some_and(|f| f.never_matches()) {
+            return None;
+        }
+        loop {
+            if let Some(dense) = &mut self.dense {
+                match dense.next(&self.offset, self.strings) {
+                    Some((state, meta, key_values)) => {
+                        if let Some(f) = &self.tag_filter {
+                            if !f.matches_dense(key_values) {
+                                continue;
+                            }
+                        }
+                        let n = Node::from_pbf_dense(
+                            state,
+                            meta,
+                            &self.offset,
+                            key_values,
+                            self.strings,
+                        );
+                        return Some(Ok(Primitive::Node(n)));
+                    }
+                    None => self.dense = None,
+                }
+            }
+            let result = match self.group.next() {
+                Some(field) => field.and_then(|(number, value)| self.element(number, value)),
+                None => {
+                    self.group = Fields(self.groups.next()?);
+                    continue;
+                }
+            };
+            match result {
+                Ok(Some(primitive)) => return Some(Ok(primitive)),
+                Ok(None) => {}
+                Err(e) => {
+                    self.groups = [].iter();
+                    self.group = Fields(&[]);
+                    self.dense = None;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl FusedIterator for LazyPrimitives<'_> {}
+
+struct DenseNodes<'l> {
+    id: PackedIter<'l, i64>,
+    lat: PackedIter<'l, i64>,
+    lon: PackedIter<'l, i64>,
+    /// the remaining keys and values, separated by `0`
+    keys_vals: &'l [u8],
+    info: Option<DenseInfo<'l>>,
+    state: DenseState,
+}
+
+struct DenseInfo<'l> {
+    version: PackedIter<'l, i32>,
+    timestamp: PackedIter<'l, i64>,
+    changeset: PackedIter<'l, i64>,
+    // `sint32` is decoded like `sint64`
+    uid: PackedIter<'l, i64>,
+    user_sid: PackedIter<'l, i64>,
+    has_user_sid: bool,
+    visible: PackedIter<'l, bool>,
+}
+
+impl<'l> DenseNodes<'l> {
+    fn parse(buf: &'l [u8]) -> Result<Self> {
+        let mut id = Packed::default();
+        let mut lat = Packed::default();
+        let mut lon = Packed::default();
+        let mut keys_vals = Packed::<i32>::default();
+        let mut info = None;
+        for field in Fields(buf) {
+            match field? {
+                (1, value) => id = value.packed()?,
+                (5, value) => info = Some(DenseInfo::parse(value.bytes()?)?),
+                (8, value) => lat = value.packed()?,
+                (9, value) => lon = value.packed()?,
+                (10, value) => keys_vals = value.packed()?,
+                _ => {}
+            }
+        }
+        Ok(Self {
+            id: id.iter(),
+            lat: lat.iter(),
+            lon: lon.iter(),
+            keys_vals: match keys_vals {
+                Packed::Varints(buf, _) => buf,
+                Packed::Slice(_) => &[],
+            },
+            info,
+            state: DenseState::default(),
+        })
+    }
+
+    fn next(
+        &mut self,
+        offset: &Offset,
+        strings: &'l StringTable,
+    ) -> Option<(DenseState, Meta<'l>, Packed<'l, i32>)> {
+        let (id, lat, lon) = (self.id.next()?, self.lat.next()?, self.lon.next()?);
+        // wrapping, so corrupt deltas can't panic
+        let state = &mut self.state;
+        state.id = state.id.wrapping_add(id);
+        state.lat = state.lat.wrapping_add(lat);
+        state.lon = state.lon.wrapping_add(lon);
+        let meta = match &mut self.info {
+            Some(info) => info.next(state, offset, strings),
+            None => Meta::default(),
+        };
+        Some((*state, meta, self.next_key_values()))
+    }
+
+    /// The keys and values up to the next `0`.
+    fn next_key_values(&mut self) -> Packed<'l, i32> {
+        let start = self.keys_vals;
+        let mut rest = start;
+        loop {
+            let end = start.len() - rest.len();
+            match read_varint(&mut rest) {
+                Some(0) => {
+                    self.keys_vals = rest;
+                    return Packed::Varints(&start[..end], PhantomData);
+                }
+                Some(_) => {
+                    read_varint(&mut rest);
+                }
+                None => {
+                    self.keys_vals = rest;
+                    return Packed::Varints(start, PhantomData);
+                }
+            }
+        }
+    }
+}
+
+impl<'l> DenseInfo<'l> {
+    fn parse(buf: &'l [u8]) -> Result<Self> {
+        let mut version = Packed::default();
+        let mut timestamp = Packed::default();
+        let mut changeset = Packed::default();
+        let mut uid = Packed::default();
+        let mut user_sid = Packed::default();
+        let mut visible = Packed::default();
+        for field in Fields(buf) {
+            match field? {
+                (1, value) => version = value.packed()?,
+                (2, value) => timestamp = value.packed()?,
+                (3, value) => changeset = value.packed()?,
+                (4, value) => uid = value.packed()?,
+                (5, value) => user_sid = value.packed()?,
+                (6, value) => visible = value.packed()?,
+                _ => {}
+            }
+        }
+        Ok(Self {
+            version: version.iter(),
+            timestamp: timestamp.iter(),
+            changeset: changeset.iter(),
+            uid: uid.iter(),
+            user_sid: user_sid.iter(),
+            has_user_sid: !user_sid.is_empty(),
+            visible: visible.iter(),
+        })
+    }
+
+    fn next(
+        &mut self,
+        state: &mut DenseState,
+        offset: &Offset,
+        strings: &'l StringTable,
+    ) -> Meta<'l> {
+        let timestamp = self.timestamp.next().unwrap_or(0);
+        let changeset = self.changeset.next().unwrap_or(0);
+        let uid = self.uid.next().unwrap_or(0) as i32;
+        let user_sid = self.user_sid.next().unwrap_or(0) as i32;
+        state.timestamp = state.timestamp.wrapping_add(timestamp);
+        state.changeset = state.changeset.wrapping_add(changeset);
+        state.uid = state.uid.wrapping_add(uid);
+        state.user_sid = state.user_sid.wrapping_add(user_sid);
+        Meta {
+            version: self.version.next().unwrap_or(0) as u32,
+            timestamp: state.timestamp.wrapping_mul(offset.date_granularity as i64),
+            changeset: ChangeSetId(state.changeset),
+            uid: state.uid,
+            user: if self.has_user_sid {
+                strings.get(state.user_sid as usize).unwrap_or("")
+            } else {
+                ""
+            },
+            visible: self.visible.next().unwrap_or(true),
+        }
+    }
+}
+
+/// The fields of a `Node`, `Way` or `Relation` message.
+struct Element<'l> {
+    id: u64,
+    keys: Packed<'l, u32>,
+    vals: Packed<'l, u32>,
+    info: Option<&'l [u8]>,
+    /// fields 8 to 10: `lat`/`lon` of nodes, `refs`/`lat`/`lon` of ways and
+    /// `roles_sid`/`memids`/`types` of relations
+    fields: [Option<Value<'l>>; 3],
+}
+
+impl<'l> Element<'l> {
+    fn parse(buf: &'l [u8]) -> Result<Self> {
+        let mut element = Self {
+            id: 0,
+            keys: Packed::default(),
+            vals: Packed::default(),
+            info: None,
+            fields: [None; 3],
+        };
+        for field in Fields(buf) {
+            match field? {
+                (1, value) => element.id = value.varint()?,
+                (2, value) => element.keys = value.packed()?,
+                (3, value) => element.vals = value.packed()?,
+                (4, value) => element.info = Some(value.bytes()?),
+                (number @ 8..=10, value) => element.fields[number as usize - 8] = Some(value),
+                _ => {}
+            }
+        }
+        Ok(element)
+    }
+
+    #[inline]
+    fn scalar(&self, i: usize) -> Result<u64> {
+        self.fields[i].map_or(Ok(0), Value::varint)
+    }
+
+    #[inline]
+    fn packed<T: Varint>(&self, i: usize) -> Result<Packed<'l, T>> {
+        self.fields[i].map_or(Ok(Packed::default()), Value::packed)
+    }
+}
+
+/// Parses an `Info` message like [`Meta::from_info`].
+fn parse_meta<'l>(
+    info: Option<&[u8]>,
+    offset: &Offset,
+    strings: &'l StringTable,
+) -> Result<Meta<'l>> {
+    let mut meta = Meta::default();
+    for field in Fields(info.unwrap_or_default()) {
+        match field? {
+            (1, value) => meta.version = value.varint()? as u32,
+            (2, value) => {
+                let timestamp = value.varint()? as i64;
+                meta.timestamp = timestamp.wrapping_mul(offset.date_granularity as i64);
+            }
+            (3, value) => meta.changeset = ChangeSetId(value.varint()? as i64),
+            (4, value) => meta.uid = value.varint()? as i32,
+            (5, value) => meta.user = strings.get(value.varint()? as u32 as usize).unwrap_or(""),
+            (6, value) => meta.visible = value.varint()? != 0,
+            _ => {}
+        }
+    }
+    Ok(meta)
+}
+
+#[inline]
+fn invalid(message: &str) -> Error {
+    Error::InvalidPbf(message.to_string())
+}
+
+/// The value of a field in the protobuf wire format.
+#[derive(Copy, Clone)]
+enum Value<'l> {
+    Varint(u64),
+    Bytes(&'l [u8]),
+    /// `fixed32` or `fixed64`, not used by the format
+    Fixed,
+}
+
+impl<'l> Value<'l> {
+    #[inline]
+    fn varint(self) -> Result<u64> {
+        match self {
+            Value::Varint(v) => Ok(v),
+            _ => Err(invalid("expected a varint")),
+        }
+    }
+
+    #[inline]
+    fn bytes(self) -> Result<&'l [u8]> {
+        match self {
+            Value::Bytes(b) => Ok(b),
+            _ => Err(invalid("expected a length-delimited field")),
+        }
+    }
+
+    #[inline]
+    fn packed<T: Varint>(self) -> Result<Packed<'l, T>> {
+        Packed::varints(self.bytes()?).ok_or_else(|| invalid("truncated packed field"))
+    }
+}
+
+/// Iterator over the `(number, value)` fields of a message.
+struct Fields<'l>(&'l [u8]);
+
+impl<'l> Fields<'l> {
+    fn read(&mut self) -> Result<(u32, Value<'l>)> {
+        let buf = &mut self.0;
+        let key = read_varint(buf).ok_or_else(|| invalid("truncated field"))?;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(buf).ok_or_else(|| invalid("truncated varint"))?),
+            1 | 5 => {
+                let len = if key & 7 == 1 { 8 } else { 4 };
+                *buf = buf.get(len..).ok_or_else(|| invalid("truncated field"))?;
+                Value::Fixed
+            }
+            2 => {
+                let len = read_varint(buf).ok_or_else(|| invalid("truncated length"))?;
+                if len > buf.len() as u64 {
+                    return Err(invalid("truncated field"));
+                }
+                let (value, rest) = buf.split_at(len as usize);
+                *buf = rest;
+                Value::Bytes(value)
+            }
+            wire_type => {
+                return Err(Error::InvalidPbf(format!(
+                    "unsupported wire type {wire_type}"
+                )))
+            }
+        };
+        Ok(((key >> 3) as u32, value))
+    }
+}
+
+impl<'l> Iterator for Fields<'l> {
+    type Item = Result<(u32, Value<'l>)>;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let field = self.read();
+        if field.is_err() {
+            self.0 = &[];
+        }
+        Some(field)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use osm_pbf_proto::{
+        osmformat::{
+            DenseInfo as PbfDenseInfo, Info as PbfInfo, Node as PbfNode,
+            PrimitiveBlock as PbfPrimitiveBlock, PrimitiveGroup as PbfPrimitiveGroup,
+            StringTable as PbfStringTable, Way as PbfWay,
+        },
+        protobuf::Message,
+    };
+
+    use super::*;
+    use crate::blob::{Blobs, Block};
+    use crate::data::{
+        node::NodeId,
+        primitive::OwnedPrimitive,
+        relation::{Member, RelationId},
+        way::WayId,
+        PrimitiveBlock,
+    };
+    use crate::header::HeaderBlock;
+    use crate::writer::PbfWriter;
+
+    const FILTERS: [&str; 6] = [
+        "amenity",
+        "amenity=bench",
+        "!name",
+        "highway!=primary",
+        "name=*",
+        "missing",
+    ];
+
+    /// Asserts that both decoders yield the same elements, for all type
+    /// selections and tag filters.
+    fn assert_same(eager: &PrimitiveBlock, lazy: &LazyBlock) {
+        let all = eager.primitives().types(PrimitiveType::all()).count();
+        assert!(all > 0);
+        for types in [
+            PrimitiveType::DEFAULT,
+            PrimitiveType::all(),
+            PrimitiveType::NODE,
+            PrimitiveType::WAY,
+            PrimitiveType::RELATION,
+            PrimitiveType::CHANGE_SET,
+            PrimitiveType::NODE | PrimitiveType::RELATION,
+        ] {
+            let expected: Vec<OwnedPrimitive> = eager
+                .primitives()
+                .types(types)
+                .map(|p| p.to_owned())
+                .collect();
+            let actual: Vec<OwnedPrimitive> = lazy
+                .primitives()
+                .types(types)
+                .map(|p| p.map(|p| p.to_owned()))
+                .collect::<Result<_>>()
+                .unwrap();
+            assert_eq!(actual, expected, "{types:?}");
+            for filter in FILTERS {
+                let filter: TagFilter = filter.parse().unwrap();
+                let expected: Vec<OwnedPrimitive> = eager
+                    .primitives()
+                    .types(types)
+                    .filter_tags(&filter)
+                    .map(|p| p.to_owned())
+                    .collect();
+                let actual: Vec<OwnedPrimitive> = lazy
+                    .primitives()
+                    .types(types)
+                    .filter_tags(&filter)
+                    .map(|p| p.map(|p| p.to_owned()))
+                    .collect::<Result<_>>()
+                    .unwrap();
+                assert_eq!(actual, expected, "{types:?} {filter:?}");
+            }
+        }
+    }
+
+    fn writer_output() -> Vec<u8> {
+        let mut writer = PbfWriter::new(Vec::new(), &HeaderBlock::new())
+            .unwrap()
+            .with_max_block_len(7);
+        let no_tags: [(&str, &str); 0] = [];
+        for id in 1..10 {
+            let meta = Meta {
+                version: id as u32,
+                timestamp: 1_356_373_800_000 + id * 60_000,
+                changeset: ChangeSetId(100 - id),
+                uid: (id % 3) as i32,
+                user: ["", "foo", "bar"][id as usize % 3],
+                visible: id != 4,
+            };
+            let tags = match id % 3 {
+                0 => vec![("amenity", "bench"), ("name", "b")],
+                1 => vec![("amenity", "cafe")],
+                _ => vec![],
+            };
+            writer
+                .write_node(NodeId(id), id * 1_000_000, -id * 2_000_000, tags, &meta)
+                .unwrap();
+        }
+        writer
+            .write_node(NodeId(10), 0, 0, no_tags, &Meta::default())
+            .unwrap();
+        for id in 1..5 {
+            let highway = if id % 2 == 0 { "primary" } else { "path" };
+            let meta = Meta {
+                version: 1,
+                user: "foo",
+                ..Meta::default()
+            };
+            writer
+                .write_way(
+                    WayId(id),
+                    (1..=id).map(NodeId),
+                    [("highway", highway)],
+                    &meta,
+                )
+                .unwrap();
+        }
+        writer.flush_block().unwrap();
+        let mut block = crate::data::builder::PrimitiveBlockBuilder::new();
+        block.add_way_with_locations(
+            WayId(5),
+            [(NodeId(1), (100, 200)), (NodeId(2), (-300, 400))],
+            [("name", "with locations")],
+            &Meta::default(),
+        );
+        writer.write_block(&block.build()).unwrap();
+        writer
+            .write_relation(
+                RelationId(1),
+                [
+                    Member::Way(WayId(1), "outer"),
+                    Member::Node(NodeId(3), ""),
+                    Member::Relation(RelationId(2), "sub"),
+                ],
+                [("type", "multipolygon")],
+                &Meta::default(),
+            )
+            .unwrap();
+        writer.write_changeset(ChangeSetId(3)).unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn same_as_eager_for_writer_output() {
+        let mut blobs = 0;
+        for blob in Blobs::from_bytes(writer_output()) {
+            let blob = blob.unwrap();
+            assert_same(&blob.decode().unwrap(), &blob.decode_lazy().unwrap());
+            blobs += 1;
+        }
+        assert_eq!(blobs, 4);
+    }
+
+    fn info(version: i32, timestamp: i64) -> PbfInfo {
+        let mut info = PbfInfo::new();
+        info.set_version(version);
+        info.set_timestamp(timestamp);
+        info.set_changeset(7);
+        info.set_uid(3);
+        info
+    }
+
+    /// A block with plain `Node` messages and infos without `user_sid`.
+    fn hand_built() -> PbfPrimitiveBlock {
+        let mut strings = PbfStringTable::new();
+        strings.s = ["", "amenity", "bench", "name", "foo"]
+            .map(|s| s.as_bytes().to_vec().into())
+            .to_vec();
+        let mut pbf = PbfPrimitiveBlock::new();
+        pbf.stringtable = Some(strings).into();
+        pbf.set_granularity(1000);
+        pbf.set_lat_offset(50);
+        pbf.set_lon_offset(-70);
+
+        let mut group = PbfPrimitiveGroup::new();
+        for id in 1..4 {
+            let mut node = PbfNode::new();
+            node.set_id(id * 10);
+            node.set_lat(id * 123);
+            node.set_lon(-id * 456);
+            if id != 2 {
+                node.keys = vec![1, 3];
+                node.vals = vec![2, 4];
+            }
+            if id != 3 {
+                node.info = Some(info(id as i32, 1_356_373_800)).into();
+            }
+            group.nodes.push(node);
+        }
+        pbf.primitivegroup.push(group);
+
+        let mut group = PbfPrimitiveGroup::new();
+        let dense = group.dense.mut_or_insert_default();
+        dense.id = vec![5, 1, 1];
+        dense.lat = vec![10, -1, 3];
+        dense.lon = vec![20, 2, -4];
+        dense.keys_vals = vec![1, 2, 0, 0, 3, 4, 0];
+        let dense_info: &mut PbfDenseInfo = dense.denseinfo.mut_or_insert_default();
+        dense_info.version = vec![1, 2, 3];
+        dense_info.timestamp = vec![1_356_373_800, 60, 60];
+        dense_info.changeset = vec![5, 1, 0];
+        dense_info.uid = vec![1, 1, -1];
+        pbf.primitivegroup.push(group);
+
+        let mut group = PbfPrimitiveGroup::new();
+        let mut way = PbfWay::new();
+        way.set_id(1);
+        way.refs = vec![10, 10, 10];
+        way.keys = vec![3];
+        way.vals = vec![4];
+        let mut way_info = info(2, 0);
+        way_info.set_visible(false);
+        way.info = Some(way_info).into();
+        group.ways.push(way);
+        pbf.primitivegroup.push(group);
+        pbf
+    }
+
+    #[test]
+    fn same_as_eager_for_plain_nodes_without_user() {
+        let pbf = hand_built();
+        let lazy = LazyBlock::parse(pbf.write_to_bytes().unwrap().into()).unwrap();
+        let eager = PrimitiveBlock::from_message(pbf).unwrap();
+        assert_same(&eager, &lazy);
+
+        let nodes: Vec<_> = lazy
+            .primitives()
+            .types(PrimitiveType::NODE)
+            .map(|p| match p.unwrap() {
+                Primitive::Node(n) => (n.id.0, n.nano_lat, n.nano_lon, n.user),
+                p => panic!("unexpected {:?}", p.to_owned()),
+            })
+            .collect();
+        assert_eq!(nodes.len(), 6);
+        assert_eq!(nodes[0], (10, 123_050, -456_070, ""));
+        assert_eq!(nodes[3], (5, 10_050, 19_930, ""));
+    }
+
+    #[test]
+    fn truncated_buffer() {
+        let data: Bytes = hand_built().write_to_bytes().unwrap().into();
+        // cutting at the end of a field of the block leaves a valid block
+        let mut boundaries = vec![0];
+        let mut fields = Fields(&data);
+        while fields.next().is_some() {
+            boundaries.push(data.len() - fields.0.len());
+        }
+        for len in 0..data.len() {
+            let result: Result<Vec<()>> = LazyBlock::parse(data.slice(..len))
+                .and_then(|block| block.primitives().map(|p| p.map(|_| ())).collect());
+            assert_eq!(result.is_ok(), boundaries.contains(&len), "{len}");
+        }
+
+        // a truncated varint inside of a group
+        let mut group = PbfPrimitiveGroup::new();
+        let dense = group.dense.mut_or_insert_default();
+        dense.id = vec![1];
+        let mut bytes = group.write_to_bytes().unwrap();
+        // field 2 (dense), field 1 (id), one byte
+        assert_eq!(bytes, [0x12, 0x03, 0x0a, 0x01, 0x02]);
+        bytes[4] = 0x82;
+        // a block with only this group
+        let data = [&[0x12, bytes.len() as u8][..], &bytes].concat();
+        let block = LazyBlock::parse(data.into()).unwrap();
+        let mut primitives = block.primitives();
+        assert!(matches!(primitives.next(), Some(Err(Error::InvalidPbf(_)))));
+        assert!(primitives.next().is_none());
+    }
+
+    #[test]
+    fn corrupt_bytes_do_not_panic() {
+        let data = hand_built().write_to_bytes().unwrap();
+        for i in 0..data.len() {
+            for byte in [0x00, 0x7f, 0x80, 0xff] {
+                let mut data = data.clone();
+                data[i] = byte;
+                if let Ok(block) = LazyBlock::parse_lossy(data.into()) {
+                    block
+                        .primitives()
+                        .types(PrimitiveType::all())
+                        .for_each(|p| {
+                            if let Ok(p) = p {
+                                let _ = p.to_owned();
+                            }
+                        });
+                }
+            }
+        }
+    }
+}
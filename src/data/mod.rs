@@ -1,33 +1,99 @@
-use std::string::FromUtf8Error;
-
 use crate::{blob::Block, error::Result};
 
 use osm_pbf_proto::osmformat::{
     Info as PbfInfo, PrimitiveBlock as PbfPrimitiveBlock, PrimitiveGroup as PbfPrimitiveGroup,
+    StringTable as PbfStringTable,
 };
 
+pub mod builder;
 pub mod changeset;
+pub mod filter;
+pub mod lazy;
 pub mod node;
+mod packed;
 pub mod primitive;
 pub mod primitive_group;
 pub mod relation;
+pub mod string_table;
 pub mod tags;
 pub mod way;
 
-#[derive(Copy, Clone, PartialEq, Eq)]
-pub struct Meta {
+use changeset::ChangeSetId;
+use node::NodeId;
+use primitive::PrimitiveType;
+use relation::RelationId;
+use string_table::StringTable;
+use way::WayId;
+
+/// Common interface of the element ids.
+pub trait ElementId: Copy + Ord {
+    /// The type of the elements identified by this id.
+    const PRIMITIVE_TYPE: PrimitiveType;
+
+    fn from_raw(id: i64) -> Self;
+
+    fn raw(self) -> i64;
+}
+
+macro_rules! impl_element_id {
+    ($id:ident, $primitive_type:ident) => {
+        impl ElementId for $id {
+            const PRIMITIVE_TYPE: PrimitiveType = PrimitiveType::$primitive_type;
+
+            #[inline(always)]
+            fn from_raw(id: i64) -> Self {
+                Self(id)
+            }
+
+            #[inline(always)]
+            fn raw(self) -> i64 {
+                self.0
+            }
+        }
+    };
+}
+
+impl_element_id!(NodeId, NODE);
+impl_element_id!(WayId, WAY);
+impl_element_id!(RelationId, RELATION);
+impl_element_id!(ChangeSetId, CHANGE_SET);
+
+/// Metadata of an element.
+///
+/// Fields that are not present in the file are `0` (or empty for `user`).
+///
+/// Only serializable, deserialize [`OwnedMeta`] instead: a borrowed `user`
+/// can't hold strings with escape sequences.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Meta<'l> {
     pub version: u32,
+    /// Milliseconds since the unix epoch.
+    pub timestamp: i64,
+    pub changeset: ChangeSetId,
+    pub uid: i32,
+    pub user: &'l str,
     pub visible: bool,
 }
 
-impl Meta {
-    fn from_info(info: &PbfInfo) -> Self {
+impl<'l> Meta<'l> {
+    fn from_info(info: &PbfInfo, offset: &Offset, strings: &'l StringTable) -> Self {
         Self {
             version: if info.has_version() {
                 info.version() as u32
             } else {
                 0
             },
+            timestamp: info
+                .timestamp()
+                .wrapping_mul(offset.date_granularity as i64),
+            changeset: ChangeSetId(info.changeset()),
+            uid: info.uid(),
+            user: if info.has_user_sid() {
+                strings.get(info.user_sid() as usize).unwrap_or("")
+            } else {
+                ""
+            },
             visible: if info.has_visible() {
                 info.visible()
             } else {
@@ -35,23 +101,93 @@ impl Meta {
             },
         }
     }
+
+    /// Seconds since the unix epoch.
+    #[inline]
+    pub fn timestamp_secs(&self) -> i64 {
+        self.timestamp.div_euclid(1000)
+    }
+}
+
+impl Meta<'_> {
+    pub fn to_owned(&self) -> OwnedMeta {
+        OwnedMeta {
+            version: self.version,
+            timestamp: self.timestamp,
+            changeset: self.changeset,
+            uid: self.uid,
+            user: self.user.to_string(),
+            visible: self.visible,
+        }
+    }
 }
 
-impl Default for Meta {
+impl Default for Meta<'_> {
     #[inline(always)]
     fn default() -> Self {
         Self {
             version: 0,
+            timestamp: 0,
+            changeset: ChangeSetId(0),
+            uid: 0,
+            user: "",
             visible: true,
         }
     }
 }
 
+/// Owned version of [`Meta`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedMeta {
+    pub version: u32,
+    /// Milliseconds since the unix epoch.
+    pub timestamp: i64,
+    pub changeset: ChangeSetId,
+    pub uid: i32,
+    pub user: String,
+    pub visible: bool,
+}
+
+impl OwnedMeta {
+    #[inline]
+    pub fn as_meta(&self) -> Meta<'_> {
+        Meta {
+            version: self.version,
+            timestamp: self.timestamp,
+            changeset: self.changeset,
+            uid: self.uid,
+            user: &self.user,
+            visible: self.visible,
+        }
+    }
+}
+
+impl Default for OwnedMeta {
+    #[inline]
+    fn default() -> Self {
+        Meta::default().to_owned()
+    }
+}
+
 #[derive(Copy, Clone)]
 struct Offset {
     lat: i64,
     lon: i64,
     granularity: i32,
+    date_granularity: i32,
+}
+
+impl Default for Offset {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            lat: 0,
+            lon: 0,
+            granularity: 100,
+            date_granularity: 1000,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Default)]
@@ -59,38 +195,225 @@ struct DenseState {
     id: i64,
     lat: i64,
     lon: i64,
+    timestamp: i64,
+    changeset: i64,
+    uid: i32,
+    user_sid: i32,
     kv_pos: usize,
 }
 
 #[derive(Clone)]
 pub struct PrimitiveBlock {
-    strings: Vec<String>,
+    strings: StringTable,
     primitive_groups: Vec<PbfPrimitiveGroup>,
     offset: Offset,
 }
 
-impl Block for PrimitiveBlock {
-    type Message = PbfPrimitiveBlock;
-
-    #[inline]
-    fn from_message(mut pbf: PbfPrimitiveBlock) -> Result<Self> {
-        let strings = if let Some(st) = pbf.stringtable.take() {
-            st.s.into_iter()
-                .map(String::from_utf8)
-                .collect::<Result<Vec<String>, FromUtf8Error>>()?
-        } else {
-            Vec::new()
-        };
-        Ok(Self {
+impl PrimitiveBlock {
+    fn from_parts(strings: StringTable, pbf: PbfPrimitiveBlock) -> Self {
+        Self {
             strings,
             offset: Offset {
                 lat: pbf.lat_offset(),
                 lon: pbf.lon_offset(),
                 granularity: pbf.granularity(),
+                date_granularity: pbf.date_granularity(),
             },
             primitive_groups: pbf.primitivegroup,
-        })
+        }
+    }
+
+    /// Like [`Block::from_message`], but replaces invalid UTF-8 in the string
+    /// table with `U+FFFD` instead of failing.
+    pub fn from_message_lossy(mut pbf: PbfPrimitiveBlock) -> Self {
+        let strings = pbf.stringtable.take().map(|st| st.s).unwrap_or_default();
+        Self::from_parts(StringTable::new_lossy(strings), pbf)
+    }
+
+    #[inline]
+    pub fn strings(&self) -> &StringTable {
+        &self.strings
+    }
+}
+
+impl Block for PrimitiveBlock {
+    const BLOB_TYPE: &'static str = "OSMData";
+
+    type Message = PbfPrimitiveBlock;
+
+    #[inline]
+    fn from_message(mut pbf: PbfPrimitiveBlock) -> Result<Self> {
+        let strings = pbf.stringtable.take().map(|st| st.s).unwrap_or_default();
+        Ok(Self::from_parts(StringTable::new(strings)?, pbf))
+    }
+
+    fn to_message(&self) -> Result<PbfPrimitiveBlock> {
+        let mut pbf = PbfPrimitiveBlock::new();
+        let mut stringtable = PbfStringTable::new();
+        stringtable.s = self.strings.raw().to_vec();
+        pbf.stringtable = Some(stringtable).into();
+        pbf.primitivegroup = self.primitive_groups.clone();
+        let default_offset = Offset::default();
+        if self.offset.granularity != default_offset.granularity {
+            pbf.set_granularity(self.offset.granularity);
+        }
+        if self.offset.lat != default_offset.lat {
+            pbf.set_lat_offset(self.offset.lat);
+        }
+        if self.offset.lon != default_offset.lon {
+            pbf.set_lon_offset(self.offset.lon);
+        }
+        if self.offset.date_granularity != default_offset.date_granularity {
+            pbf.set_date_granularity(self.offset.date_granularity);
+        }
+        Ok(pbf)
     }
 }
 
 pub type OSMDataBlob = crate::blob::Blob<PrimitiveBlock>;
+
+impl OSMDataBlob {
+    /// Like [`decode`](crate::blob::Blob::decode), but replaces invalid UTF-8
+    /// in the string table with `U+FFFD` instead of failing.
+    pub fn decode_lossy(&self) -> Result<PrimitiveBlock> {
+        Ok(PrimitiveBlock::from_message_lossy(self.decode_message()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use osm_pbf_proto::osmformat::{
+        relation::MemberType as PbfMemberType, Node as PbfNode, Relation as PbfRelation,
+        StringTable as PbfStringTable,
+    };
+    use osm_pbf_proto::protobuf::EnumOrUnknown;
+
+    use primitive::Primitive;
+    use relation::Member;
+
+    /// Plain nodes 1-2, dense nodes 3-5 and relation 6, each in its own group.
+    fn block() -> PrimitiveBlock {
+        let mut strings = PbfStringTable::new();
+        strings.s = ["", "amenity", "bench", "name", "foo", "outer"]
+            .map(|s| s.as_bytes().to_vec().into())
+            .to_vec();
+        let mut pbf = PbfPrimitiveBlock::new();
+        pbf.stringtable = Some(strings).into();
+
+        let mut group = PbfPrimitiveGroup::new();
+        for id in 1..3 {
+            let mut node = PbfNode::new();
+            node.set_id(id);
+            node.keys = vec![1];
+            node.vals = vec![2];
+            group.nodes.push(node);
+        }
+        pbf.primitivegroup.push(group);
+
+        let mut group = PbfPrimitiveGroup::new();
+        let dense = group.dense.mut_or_insert_default();
+        dense.id = vec![3, 1, 1];
+        dense.lat = vec![0, 0, 0];
+        dense.lon = vec![0, 0, 0];
+        dense.keys_vals = vec![1, 2, 0, 0, 3, 4, 1, 2, 0];
+        pbf.primitivegroup.push(group);
+
+        let mut group = PbfPrimitiveGroup::new();
+        let mut relation = PbfRelation::new();
+        relation.set_id(6);
+        relation.memids = vec![10, 5, -3];
+        relation.types = [PbfMemberType::NODE, PbfMemberType::WAY, PbfMemberType::WAY]
+            .map(EnumOrUnknown::new)
+            .to_vec();
+        relation.roles_sid = vec![5, 0, 5];
+        group.relations.push(relation);
+        pbf.primitivegroup.push(group);
+
+        PrimitiveBlock::from_message(pbf).unwrap()
+    }
+
+    fn node_tags(block: &PrimitiveBlock) -> Vec<(i64, Vec<(&str, &str)>)> {
+        block
+            .primitives()
+            .filter_map(|p| match p {
+                Primitive::Node(n) => Some((n.id.0, n.tags().collect())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn string_table_from_bytes() {
+        let block = block();
+        let tags = node_tags(&block);
+        assert_eq!(tags[0], (1, vec![("amenity", "bench")]));
+    }
+
+    #[test]
+    fn groups_start_at_their_first_element() {
+        let block = block();
+        let ids: Vec<i64> = node_tags(&block).into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+        let relations = block
+            .primitives()
+            .filter(|p| matches!(p, Primitive::Relation(_)))
+            .count();
+        assert_eq!(relations, 1);
+    }
+
+    #[test]
+    fn dense_tags_end_at_delimiter() {
+        let block = block();
+        assert_eq!(
+            node_tags(&block)[2..],
+            [
+                (3, vec![("amenity", "bench")]),
+                (4, vec![]),
+                (5, vec![("name", "foo"), ("amenity", "bench")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn relation_member_ids_are_delta_coded() {
+        let block = block();
+        let Some(Primitive::Relation(relation)) = block.primitives().last() else {
+            panic!("expected a relation");
+        };
+        let members: Vec<_> = relation
+            .members()
+            .map(|m| match m {
+                Member::Node(id, role) => ('n', id.0, role),
+                Member::Way(id, role) => ('w', id.0, role),
+                Member::Relation(id, role) => ('r', id.0, role),
+            })
+            .collect();
+        assert_eq!(
+            members,
+            [('n', 10, "outer"), ('w', 15, ""), ('w', 12, "outer")]
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_owned_from_borrowed() {
+        let meta = Meta {
+            version: 2,
+            timestamp: 1_356_373_800_000,
+            changeset: ChangeSetId(3),
+            uid: 4,
+            user: "a\"b\\c",
+            visible: false,
+        };
+        let json = serde_json::to_string(&meta).unwrap();
+        assert!(json.contains(r#""user":"a\"b\\c""#));
+        let owned: OwnedMeta = serde_json::from_str(&json).unwrap();
+        assert_eq!(owned.as_meta(), meta);
+
+        let member = Member::Node(NodeId(1), "\"stop\"");
+        let json = serde_json::to_string(&member).unwrap();
+        let owned: relation::OwnedMember = serde_json::from_str(&json).unwrap();
+        assert_eq!(owned.as_member(), member);
+    }
+}
@@ -3,11 +3,15 @@ use std::ops::Deref;
 use osm_pbf_proto::osmformat::Node as PbfNode;
 
 use super::{
+    packed::Packed,
+    string_table::StringTable,
     tags::{NodeTagFields, Tags},
-    DenseState, Meta,
+    DenseState, Meta, OwnedMeta,
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct NodeId(pub i64);
 
 pub struct Node<'l> {
@@ -17,48 +21,78 @@ pub struct Node<'l> {
     /// Longitude in nanodegrees
     pub nano_lon: i64,
 
-    strings: &'l [String],
+    strings: &'l StringTable,
     tags: NodeTagFields<'l>,
-    meta: Meta,
+    meta: Meta<'l>,
 }
 
-impl Deref for Node<'_> {
-    type Target = Meta;
+impl<'l> Deref for Node<'l> {
+    type Target = Meta<'l>;
     #[inline]
-    fn deref(&self) -> &Meta {
+    fn deref(&self) -> &Meta<'l> {
         &self.meta
     }
 }
 
 impl<'l> Node<'l> {
     #[inline]
-    pub(super) fn from_pbf(n: &'l PbfNode, offset: &super::Offset, strings: &'l [String]) -> Self {
-        Self {
-            id: NodeId(n.id()),
+    pub(super) fn from_pbf(
+        n: &'l PbfNode,
+        offset: &super::Offset,
+        strings: &'l StringTable,
+    ) -> Self {
+        Self::from_parts(
+            n.id(),
+            n.lat(),
+            n.lon(),
+            offset,
+            NodeTagFields::Normal(Packed::Slice(&n.keys), Packed::Slice(&n.vals)),
+            Meta::from_info(&n.info, offset, strings),
             strings,
-            nano_lat: offset.lat + n.lat() * offset.granularity as i64,
-            nano_lon: offset.lon + n.lon() * offset.granularity as i64,
-            tags: NodeTagFields::Normal(&n.keys, &n.vals),
-            meta: Meta::from_info(&n.info),
-        }
+        )
     }
 
     #[inline]
     pub(super) fn from_pbf_dense(
         d: DenseState,
-        version: u32,
-        visible: bool,
+        meta: Meta<'l>,
         offset: &super::Offset,
-        key_values: &'l [i32],
-        strings: &'l [String],
+        key_values: Packed<'l, i32>,
+        strings: &'l StringTable,
+    ) -> Self {
+        Self::from_parts(
+            d.id,
+            d.lat,
+            d.lon,
+            offset,
+            NodeTagFields::Dense(key_values),
+            meta,
+            strings,
+        )
+    }
+
+    /// `lat` and `lon` in units of the granularity of the block.
+    #[inline]
+    pub(super) fn from_parts(
+        id: i64,
+        lat: i64,
+        lon: i64,
+        offset: &super::Offset,
+        tags: NodeTagFields<'l>,
+        meta: Meta<'l>,
+        strings: &'l StringTable,
     ) -> Self {
         Self {
-            id: NodeId(d.id),
-            nano_lat: offset.lat + d.lat * offset.granularity as i64,
-            nano_lon: offset.lon + d.lon * offset.granularity as i64,
+            id: NodeId(id),
+            nano_lat: lat
+                .wrapping_mul(offset.granularity as i64)
+                .wrapping_add(offset.lat),
+            nano_lon: lon
+                .wrapping_mul(offset.granularity as i64)
+                .wrapping_add(offset.lon),
             strings,
-            tags: NodeTagFields::Dense(key_values),
-            meta: Meta { version, visible },
+            tags,
+            meta,
         }
     }
 
@@ -76,4 +110,73 @@ impl<'l> Node<'l> {
     pub fn tags(&self) -> Tags<'l> {
         self.tags.iter_with_strings(self.strings)
     }
+
+    pub fn to_owned(&self) -> OwnedNode {
+        OwnedNode {
+            id: self.id,
+            nano_lat: self.nano_lat,
+            nano_lon: self.nano_lon,
+            tags: self
+                .tags()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            meta: self.meta.to_owned(),
+        }
+    }
+}
+
+/// Serialized like [`OwnedNode`].
+#[cfg(feature = "serde")]
+impl serde::Serialize for Node<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("OwnedNode", 5)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("nano_lat", &self.nano_lat)?;
+        state.serialize_field("nano_lon", &self.nano_lon)?;
+        state.serialize_field("tags", &self.tags())?;
+        state.serialize_field("meta", &self.meta)?;
+        state.end()
+    }
+}
+
+/// Owned version of [`Node`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedNode {
+    pub id: NodeId,
+    /// Latitude in nanodegrees
+    pub nano_lat: i64,
+    /// Longitude in nanodegrees
+    pub nano_lon: i64,
+    pub tags: Vec<(String, String)>,
+    pub meta: OwnedMeta,
+}
+
+impl Deref for OwnedNode {
+    type Target = OwnedMeta;
+    #[inline]
+    fn deref(&self) -> &OwnedMeta {
+        &self.meta
+    }
+}
+
+impl OwnedNode {
+    /// Latitude in degrees.
+    #[inline(always)]
+    pub fn lat(&self) -> f64 {
+        self.nano_lat as f64 * 1e-9
+    }
+    /// Longitude in degrees.
+    #[inline(always)]
+    pub fn lon(&self) -> f64 {
+        self.nano_lon as f64 * 1e-9
+    }
+}
+
+impl From<&Node<'_>> for OwnedNode {
+    #[inline]
+    fn from(node: &Node<'_>) -> Self {
+        node.to_owned()
+    }
 }
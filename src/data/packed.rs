@@ -0,0 +1,151 @@
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::slice;
+
+use osm_pbf_proto::{osmformat::relation::MemberType as PbfMemberType, protobuf::EnumOrUnknown};
+
+/// Reads a base 128 varint; `None` when the buffer ends before the last byte.
+#[inline]
+pub(crate) fn read_varint(buf: &mut &[u8]) -> Option<u64> {
+    let mut value = 0;
+    for (i, &b) in buf.iter().enumerate() {
+        if i < 10 {
+            value |= ((b & 0x7f) as u64) << (7 * i);
+        }
+        if b & 0x80 == 0 {
+            *buf = &buf[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// A scalar type of a packed repeated field.
+pub(crate) trait Varint: Copy {
+    fn from_varint(v: u64) -> Self;
+}
+
+/// `uint32`
+impl Varint for u32 {
+    #[inline(always)]
+    fn from_varint(v: u64) -> Self {
+        v as u32
+    }
+}
+
+/// `int32` (the `sint32` fields of dense nodes are decoded as `i64`)
+impl Varint for i32 {
+    #[inline(always)]
+    fn from_varint(v: u64) -> Self {
+        v as i32
+    }
+}
+
+/// `sint64`
+impl Varint for i64 {
+    #[inline(always)]
+    fn from_varint(v: u64) -> Self {
+        (v >> 1) as i64 ^ -((v & 1) as i64)
+    }
+}
+
+impl Varint for bool {
+    #[inline(always)]
+    fn from_varint(v: u64) -> Self {
+        v != 0
+    }
+}
+
+impl Varint for EnumOrUnknown<PbfMemberType> {
+    #[inline(always)]
+    fn from_varint(v: u64) -> Self {
+        EnumOrUnknown::from_i32(v as i32)
+    }
+}
+
+/// A repeated field, either decoded by the generated message or still
+/// encoded as packed varints in the buffer of the block.
+#[derive(Copy, Clone)]
+pub(crate) enum Packed<'l, T> {
+    Slice(&'l [T]),
+    /// The buffer ends with a complete varint (see [`Packed::varints`]).
+    Varints(&'l [u8], PhantomData<T>),
+}
+
+impl<'l, T: Varint> Packed<'l, T> {
+    /// Returns `None` when the last varint is truncated.
+    #[inline]
+    pub fn varints(buf: &'l [u8]) -> Option<Self> {
+        match buf.last() {
+            Some(b) if b & 0x80 != 0 => None,
+            _ => Some(Packed::Varints(buf, PhantomData)),
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            Packed::Slice(s) => s.len(),
+            Packed::Varints(buf, _) => buf.iter().filter(|&&b| b & 0x80 == 0).count(),
+        }
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Packed::Slice(s) => s.is_empty(),
+            Packed::Varints(buf, _) => buf.is_empty(),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> PackedIter<'l, T> {
+        match *self {
+            Packed::Slice(s) => PackedIter::Slice(s.iter()),
+            Packed::Varints(buf, _) => PackedIter::Varints(buf, PhantomData),
+        }
+    }
+}
+
+impl<T> Default for Packed<'_, T> {
+    #[inline]
+    fn default() -> Self {
+        Packed::Slice(&[])
+    }
+}
+
+#[derive(Clone)]
+pub(crate) enum PackedIter<'l, T> {
+    Slice(slice::Iter<'l, T>),
+    Varints(&'l [u8], PhantomData<T>),
+}
+
+impl<T: Varint> Iterator for PackedIter<'_, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<T> {
+        match self {
+            PackedIter::Slice(iter) => iter.next().copied(),
+            PackedIter::Varints(buf, _) => read_varint(buf).map(T::from_varint),
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            PackedIter::Slice(iter) => iter.size_hint(),
+            PackedIter::Varints(buf, _) => (buf.len().div_ceil(10), Some(buf.len())),
+        }
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        match self {
+            PackedIter::Slice(iter) => iter.len(),
+            PackedIter::Varints(buf, _) => buf.iter().filter(|&&b| b & 0x80 == 0).count(),
+        }
+    }
+}
+
+impl<T: Varint> FusedIterator for PackedIter<'_, T> {}
@@ -1,12 +1,25 @@
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
+
 use bitflags::bitflags;
 use osm_pbf_proto::osmformat::PrimitiveGroup as PbfPrimitiveGroup;
 
 use super::{
-    changeset::ChangeSet, node::Node, primitive_group::PrimitiveGroup, relation::Relation,
-    way::Way, DenseState, Offset, PrimitiveBlock,
+    changeset::{ChangeSet, ChangeSetId},
+    filter::{CompiledTagFilter, TagFilter},
+    node::{Node, OwnedNode},
+    packed::Packed,
+    primitive_group::PrimitiveGroup,
+    relation::{OwnedRelation, Relation},
+    string_table::StringTable,
+    way::{OwnedWay, Way},
+    DenseState, Meta, OSMDataBlob, Offset, OwnedMeta, PrimitiveBlock,
 };
+use crate::error::Result;
 
 bitflags! {
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
     pub struct PrimitiveType: u32 {
         const NODE = 1;
         const WAY = 2;
@@ -25,14 +38,108 @@ pub enum Primitive<'l> {
     ChangeSet(super::changeset::ChangeSet),
 }
 
+impl Primitive<'_> {
+    pub fn to_owned(&self) -> OwnedPrimitive {
+        match self {
+            Primitive::Node(n) => OwnedPrimitive::Node(n.to_owned()),
+            Primitive::Way(w) => OwnedPrimitive::Way(w.to_owned()),
+            Primitive::Relation(r) => OwnedPrimitive::Relation(r.to_owned()),
+            Primitive::ChangeSet(c) => OwnedPrimitive::ChangeSet(c.clone()),
+        }
+    }
+}
+
+/// Owned version of [`Primitive`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum OwnedPrimitive {
+    Node(OwnedNode),
+    Way(OwnedWay),
+    Relation(OwnedRelation),
+    ChangeSet(ChangeSet),
+}
+
+impl OwnedPrimitive {
+    #[inline]
+    pub fn primitive_type(&self) -> PrimitiveType {
+        match self {
+            OwnedPrimitive::Node(_) => PrimitiveType::NODE,
+            OwnedPrimitive::Way(_) => PrimitiveType::WAY,
+            OwnedPrimitive::Relation(_) => PrimitiveType::RELATION,
+            OwnedPrimitive::ChangeSet(_) => PrimitiveType::CHANGE_SET,
+        }
+    }
+
+    /// The raw id of the element.
+    #[inline]
+    pub fn id(&self) -> i64 {
+        match self {
+            OwnedPrimitive::Node(n) => n.id.0,
+            OwnedPrimitive::Way(w) => w.id.0,
+            OwnedPrimitive::Relation(r) => r.id.0,
+            OwnedPrimitive::ChangeSet(c) => c.id.0,
+        }
+    }
+
+    /// The metadata of the element (`None` for changesets).
+    #[inline]
+    pub fn meta(&self) -> Option<&OwnedMeta> {
+        match self {
+            OwnedPrimitive::Node(n) => Some(&n.meta),
+            OwnedPrimitive::Way(w) => Some(&w.meta),
+            OwnedPrimitive::Relation(r) => Some(&r.meta),
+            OwnedPrimitive::ChangeSet(_) => None,
+        }
+    }
+}
+
+/// Iterator over the owned primitives of a sequence of data blobs,
+/// like [`Blobs`](crate::Blobs).
+pub struct OwnedPrimitives<I> {
+    blobs: I,
+    pending: VecDeque<OwnedPrimitive>,
+}
+
+impl<I: Iterator<Item = Result<OSMDataBlob>>> OwnedPrimitives<I> {
+    #[inline]
+    pub fn new(blobs: I) -> Self {
+        Self {
+            blobs,
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<OSMDataBlob>>> Iterator for OwnedPrimitives<I> {
+    type Item = Result<OwnedPrimitive>;
+
+    fn next(&mut self) -> Option<Result<OwnedPrimitive>> {
+        loop {
+            if let Some(primitive) = self.pending.pop_front() {
+                return Some(Ok(primitive));
+            }
+            let block = match self.blobs.next()?.and_then(|blob| blob.decode()) {
+                Ok(block) => block,
+                Err(e) => return Some(Err(e)),
+            };
+            self.pending
+                .extend(block.primitives().map(|p| p.to_owned()));
+        }
+    }
+}
+
+impl<I: FusedIterator<Item = Result<OSMDataBlob>>> FusedIterator for OwnedPrimitives<I> {}
+
 pub struct Primitives<'l> {
-    strings: &'l [String],
+    strings: &'l StringTable,
     groups: &'l [PbfPrimitiveGroup],
     filter: PrimitiveType,
     group_pos: usize,
     prim_pos: usize,
     offset: Offset,
     dense_state: DenseState,
+    tag_filter: Option<CompiledTagFilter>,
 }
 
 impl PrimitiveBlock {
@@ -45,6 +152,7 @@ impl PrimitiveBlock {
             prim_pos: 0,
             offset: self.offset,
             dense_state: DenseState::default(),
+            tag_filter: None,
         }
     }
 }
@@ -59,6 +167,7 @@ impl PrimitiveGroup<'_> {
             prim_pos: 0,
             offset: self.block.offset,
             dense_state: DenseState::default(),
+            tag_filter: None,
         }
     }
 }
@@ -69,16 +178,39 @@ impl<'l> Primitives<'l> {
         self.filter = types;
         self
     }
+
+    /// Only yields elements with tags matching the filter.
+    ///
+    /// The filter is resolved to the string table of the block once, so the
+    /// tags are tested on indices without building any `&str`.
+    #[inline]
+    pub fn filter_tags(mut self, filter: &TagFilter) -> Self {
+        self.tag_filter = Some(filter.compile(self.strings));
+        self
+    }
+
+    #[inline]
+    fn accepts(&self, keys: &[u32], values: &[u32]) -> bool {
+        self.tag_filter
+            .as_ref()
+            .is_none_or(|f| f.matches_fields(Packed::Slice(keys), Packed::Slice(values)))
+    }
 }
 
 impl<'l> Iterator for Primitives<'l> {
     type Item = Primitive<'l>;
     fn next(&mut self) -> Option<Self::Item> {
+        if self.tag_filter.as_ref().is_some_and(|f| f.never_matches()) {
+            return None;
+        }
         loop {
             let group = self.groups.get(self.group_pos)?;
             if self.filter.contains(PrimitiveType::NODE) && !group.nodes.is_empty() {
                 if let Some(n) = group.nodes.get(self.prim_pos) {
                     self.prim_pos += 1;
+                    if !self.accepts(&n.keys, &n.vals) {
+                        continue;
+                    }
                     let n = Node::from_pbf(n, &self.offset, self.strings);
                     return Some(Primitive::Node(n));
                 }
@@ -91,35 +223,60 @@ impl<'l> Iterator for Primitives<'l> {
                     dense.lon.get(prim_pos).copied(),
                 ) {
                     self.prim_pos = prim_pos + 1;
-                    self.dense_state.id += id;
-                    self.dense_state.lat += lat;
-                    self.dense_state.lon += lon;
-
-                    let version;
-                    let visible;
-                    if let Some(info) = dense.denseinfo.as_ref() {
-                        version = info.version.get(prim_pos).copied().unwrap_or(0) as u32;
-                        visible = info.visible.get(prim_pos).copied().unwrap_or(true);
+                    // wrapping, so corrupt deltas can't panic
+                    self.dense_state.id = self.dense_state.id.wrapping_add(id);
+                    self.dense_state.lat = self.dense_state.lat.wrapping_add(lat);
+                    self.dense_state.lon = self.dense_state.lon.wrapping_add(lon);
+
+                    let meta = if let Some(info) = dense.denseinfo.as_ref() {
+                        let state = &mut self.dense_state;
+                        let timestamp = info.timestamp.get(prim_pos).copied().unwrap_or(0);
+                        let changeset = info.changeset.get(prim_pos).copied().unwrap_or(0);
+                        let uid = info.uid.get(prim_pos).copied().unwrap_or(0);
+                        let user_sid = info.user_sid.get(prim_pos).copied().unwrap_or(0);
+                        state.timestamp = state.timestamp.wrapping_add(timestamp);
+                        state.changeset = state.changeset.wrapping_add(changeset);
+                        state.uid = state.uid.wrapping_add(uid);
+                        state.user_sid = state.user_sid.wrapping_add(user_sid);
+                        Meta {
+                            version: info.version.get(prim_pos).copied().unwrap_or(0) as u32,
+                            timestamp: state
+                                .timestamp
+                                .wrapping_mul(self.offset.date_granularity as i64),
+                            changeset: ChangeSetId(state.changeset),
+                            uid: state.uid,
+                            user: if info.user_sid.is_empty() {
+                                ""
+                            } else {
+                                self.strings.get(state.user_sid as usize).unwrap_or("")
+                            },
+                            visible: info.visible.get(prim_pos).copied().unwrap_or(true),
+                        }
                     } else {
-                        version = 0;
-                        visible = true;
-                    }
+                        Meta::default()
+                    };
 
                     // find range for key-value pairs
-                    let kv_from = self.dense_state.kv_pos;
-                    while let Some(k) = dense.keys_vals.get(self.dense_state.kv_pos).copied() {
+                    let kv_from = self.dense_state.kv_pos.min(dense.keys_vals.len());
+                    let mut kv_to = kv_from;
+                    while let Some(k) = dense.keys_vals.get(kv_to).copied() {
                         if k == 0 {
-                            self.dense_state.kv_pos += 1;
-                        } else {
-                            self.dense_state.kv_pos += 2;
+                            self.dense_state.kv_pos = kv_to + 1;
+                            break;
+                        }
+                        kv_to = (kv_to + 2).min(dense.keys_vals.len());
+                        self.dense_state.kv_pos = kv_to;
+                    }
+                    let key_values = Packed::Slice(&dense.keys_vals[kv_from..kv_to]);
+                    if let Some(f) = &self.tag_filter {
+                        if !f.matches_dense(key_values) {
+                            continue;
                         }
                     }
-                    let key_values = &dense.keys_vals[kv_from..self.dense_state.kv_pos];
 
                     let n = Node::from_pbf_dense(
                         self.dense_state,
-                        version,
-                        visible,
+                        meta,
                         &self.offset,
                         key_values,
                         self.strings,
@@ -131,13 +288,19 @@ impl<'l> Iterator for Primitives<'l> {
             } else if self.filter.contains(PrimitiveType::WAY) && !group.ways.is_empty() {
                 if let Some(w) = group.ways.get(self.prim_pos) {
                     self.prim_pos += 1;
-                    let w = Way::from_pbf(w, self.strings);
+                    if !self.accepts(&w.keys, &w.vals) {
+                        continue;
+                    }
+                    let w = Way::from_pbf(w, &self.offset, self.strings);
                     return Some(Primitive::Way(w));
                 }
             } else if self.filter.contains(PrimitiveType::RELATION) && !group.relations.is_empty() {
                 if let Some(r) = group.relations.get(self.prim_pos) {
                     self.prim_pos += 1;
-                    let r = Relation::from_pbf(r, self.strings);
+                    if !self.accepts(&r.keys, &r.vals) {
+                        continue;
+                    }
+                    let r = Relation::from_pbf(r, &self.offset, self.strings);
                     return Some(Primitive::Relation(r));
                 }
             } else if self.filter.contains(PrimitiveType::CHANGE_SET)
@@ -145,11 +308,61 @@ impl<'l> Iterator for Primitives<'l> {
             {
                 if let Some(c) = group.changesets.get(self.prim_pos) {
                     self.prim_pos += 1;
+                    // changesets have no tags
+                    if !self.accepts(&[], &[]) {
+                        continue;
+                    }
                     let c = ChangeSet::from_pbf(c);
                     return Some(Primitive::ChangeSet(c));
                 }
             }
             self.group_pos += 1;
+            self.prim_pos = 0;
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use osm_pbf_proto::osmformat::{
+        DenseInfo as PbfDenseInfo, PrimitiveBlock as PbfPrimitiveBlock, Way as PbfWay,
+    };
+
+    use super::*;
+    use crate::blob::Block;
+
+    #[test]
+    fn overflowing_deltas_wrap() {
+        let mut pbf = PbfPrimitiveBlock::new();
+        let mut group = PbfPrimitiveGroup::new();
+        let dense = group.dense.mut_or_insert_default();
+        dense.id = vec![i64::MAX, 1];
+        dense.lat = vec![i64::MAX, 1];
+        dense.lon = vec![i64::MIN, -1];
+        let info: &mut PbfDenseInfo = dense.denseinfo.mut_or_insert_default();
+        info.timestamp = vec![i64::MAX, 1];
+        info.changeset = vec![i64::MAX, 1];
+        info.uid = vec![i32::MAX, 1];
+        info.user_sid = vec![i32::MAX, 1];
+        pbf.primitivegroup.push(group);
+        let mut group = PbfPrimitiveGroup::new();
+        let mut way = PbfWay::new();
+        way.set_id(1);
+        way.refs = vec![i64::MAX, 1];
+        group.ways.push(way);
+        pbf.primitivegroup.push(group);
+
+        let block = PrimitiveBlock::from_message(pbf).unwrap();
+        let primitives: Vec<_> = block.primitives().map(|p| p.to_owned()).collect();
+        assert_eq!(primitives.len(), 3);
+        let OwnedPrimitive::Node(node) = &primitives[1] else {
+            panic!("expected a node");
+        };
+        assert_eq!(node.id.0, i64::MIN);
+        assert_eq!(node.meta.uid, i32::MIN);
+        let OwnedPrimitive::Way(way) = &primitives[2] else {
+            panic!("expected a way");
+        };
+        assert_eq!(way.refs[1].0, i64::MIN);
+    }
+}
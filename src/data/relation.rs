@@ -7,56 +7,92 @@ use osm_pbf_proto::{
 
 use super::{
     node::NodeId,
+    packed::{Packed, PackedIter},
+    string_table::StringTable,
     tags::{TagFields, Tags},
     way::WayId,
-    Meta,
+    Meta, OwnedMeta,
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct RelationId(pub i64);
 
+pub(super) type MemberFields<'l> = (
+    Packed<'l, i32>,
+    Packed<'l, i64>,
+    Packed<'l, EnumOrUnknown<PbfMemberType>>,
+);
+
 pub struct Relation<'l> {
     pub id: RelationId,
 
-    strings: &'l [String],
+    strings: &'l StringTable,
 
-    roles_sid: &'l [i32],
-    memids: &'l [i64],
-    types: &'l [EnumOrUnknown<PbfMemberType>],
+    roles_sid: Packed<'l, i32>,
+    memids: Packed<'l, i64>,
+    types: Packed<'l, EnumOrUnknown<PbfMemberType>>,
 
     tags: TagFields<'l>,
-    meta: Meta,
+    meta: Meta<'l>,
 }
 
-impl Deref for Relation<'_> {
-    type Target = Meta;
+impl<'l> Deref for Relation<'l> {
+    type Target = Meta<'l>;
     #[inline]
-    fn deref(&self) -> &Meta {
+    fn deref(&self) -> &Meta<'l> {
         &self.meta
     }
 }
 
 impl<'l> Relation<'l> {
     #[inline]
-    pub(crate) fn from_pbf(r: &'l PbfRelation, strings: &'l [String]) -> Self {
+    pub(super) fn from_pbf(
+        r: &'l PbfRelation,
+        offset: &super::Offset,
+        strings: &'l StringTable,
+    ) -> Self {
+        Self::from_parts(
+            r.id(),
+            (
+                Packed::Slice(&r.roles_sid),
+                Packed::Slice(&r.memids),
+                Packed::Slice(&r.types),
+            ),
+            TagFields(Packed::Slice(&r.keys), Packed::Slice(&r.vals)),
+            Meta::from_info(&r.info, offset, strings),
+            strings,
+        )
+    }
+
+    /// The `(roles_sid, memids, types)` fields of the members.
+    #[inline]
+    pub(super) fn from_parts(
+        id: i64,
+        (roles_sid, memids, types): MemberFields<'l>,
+        tags: TagFields<'l>,
+        meta: Meta<'l>,
+        strings: &'l StringTable,
+    ) -> Self {
         Self {
-            id: RelationId(r.id()),
+            id: RelationId(id),
             strings,
-            roles_sid: &r.roles_sid,
-            memids: &r.memids,
-            types: &r.types,
-            tags: TagFields(&r.keys, &r.vals),
-            meta: Meta::from_info(&r.info),
+            roles_sid,
+            memids,
+            types,
+            tags,
+            meta,
         }
     }
 
     pub fn members(&self) -> Members<'l> {
         Members {
             strings: self.strings,
-            i: 0,
-            roles: self.roles_sid,
-            member_ids: self.memids,
-            member_types: self.types,
+            current: 0,
+            roles: self.roles_sid.iter(),
+            member_ids: self.memids.iter(),
+            member_types: self.types.iter(),
         }
     }
 
@@ -64,21 +100,115 @@ impl<'l> Relation<'l> {
     pub fn tags(&self) -> Tags<'l> {
         self.tags.iter_with_strings(self.strings)
     }
+
+    pub fn to_owned(&self) -> OwnedRelation {
+        OwnedRelation {
+            id: self.id,
+            members: self.members().map(|m| m.to_owned()).collect(),
+            tags: self
+                .tags()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            meta: self.meta.to_owned(),
+        }
+    }
+}
+
+/// Serialized like [`OwnedRelation`].
+#[cfg(feature = "serde")]
+impl serde::Serialize for Relation<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("OwnedRelation", 4)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("members", &self.members())?;
+        state.serialize_field("tags", &self.tags())?;
+        state.serialize_field("meta", &self.meta)?;
+        state.end()
+    }
 }
 
+/// A member of a relation with its role.
+///
+/// Only serializable, deserialize [`OwnedMember`] instead.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Member<'l> {
     Node(NodeId, &'l str),
     Way(WayId, &'l str),
     Relation(RelationId, &'l str),
 }
 
+impl Member<'_> {
+    /// The role of the member.
+    #[inline]
+    pub fn role(&self) -> &str {
+        match *self {
+            Member::Node(_, role) | Member::Way(_, role) | Member::Relation(_, role) => role,
+        }
+    }
+
+    pub fn to_owned(&self) -> OwnedMember {
+        match *self {
+            Member::Node(id, role) => OwnedMember::Node(id, role.to_string()),
+            Member::Way(id, role) => OwnedMember::Way(id, role.to_string()),
+            Member::Relation(id, role) => OwnedMember::Relation(id, role.to_string()),
+        }
+    }
+}
+
+/// Owned version of [`Member`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OwnedMember {
+    Node(NodeId, String),
+    Way(WayId, String),
+    Relation(RelationId, String),
+}
+
+impl OwnedMember {
+    #[inline]
+    pub fn as_member(&self) -> Member<'_> {
+        match self {
+            OwnedMember::Node(id, role) => Member::Node(*id, role),
+            OwnedMember::Way(id, role) => Member::Way(*id, role),
+            OwnedMember::Relation(id, role) => Member::Relation(*id, role),
+        }
+    }
+}
+
+/// Owned version of [`Relation`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedRelation {
+    pub id: RelationId,
+    pub members: Vec<OwnedMember>,
+    pub tags: Vec<(String, String)>,
+    pub meta: OwnedMeta,
+}
+
+impl Deref for OwnedRelation {
+    type Target = OwnedMeta;
+    #[inline]
+    fn deref(&self) -> &OwnedMeta {
+        &self.meta
+    }
+}
+
+impl From<&Relation<'_>> for OwnedRelation {
+    #[inline]
+    fn from(relation: &Relation<'_>) -> Self {
+        relation.to_owned()
+    }
+}
+
 #[derive(Clone)]
 pub struct Members<'l> {
-    strings: &'l [String],
-    i: usize,
-    roles: &'l [i32],
-    member_ids: &'l [i64],
-    member_types: &'l [EnumOrUnknown<PbfMemberType>],
+    strings: &'l StringTable,
+    current: i64,
+    roles: PackedIter<'l, i32>,
+    member_ids: PackedIter<'l, i64>,
+    member_types: PackedIter<'l, EnumOrUnknown<PbfMemberType>>,
 }
 
 impl<'l> IntoIterator for Relation<'l> {
@@ -104,19 +234,14 @@ impl<'l> Iterator for Members<'l> {
     #[inline]
     fn next(&mut self) -> Option<Member<'l>> {
         loop {
-            let pos = self.i;
-            self.i += 1;
-            let role_str_id = *self.roles.get(pos)? as usize;
-            let member_id = *self.member_ids.get(pos)?;
-            let member_type = match self.member_types.get(pos)?.enum_value() {
+            let role_str_id = self.roles.next()? as usize;
+            self.current = self.current.wrapping_add(self.member_ids.next()?);
+            let member_id = self.current;
+            let member_type = match self.member_types.next()?.enum_value() {
                 Ok(member_type) => member_type,
                 Err(_) => continue,
             };
-            let role_str = self
-                .strings
-                .get(role_str_id)
-                .map(Deref::deref)
-                .unwrap_or("");
+            let role_str = self.strings.get(role_str_id).unwrap_or("");
             return Some(match member_type {
                 PbfMemberType::NODE => Member::Node(NodeId(member_id), role_str),
                 PbfMemberType::WAY => Member::Way(WayId(member_id), role_str),
@@ -125,3 +250,11 @@ impl<'l> Iterator for Members<'l> {
         }
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Members<'_> {
+    /// Serializes the remaining members as a sequence.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.clone())
+    }
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::iter::FusedIterator;
+use std::str;
+
+use bytes::Bytes;
+
+use crate::error::Result;
+
+/// The string table of a [`PrimitiveBlock`](super::PrimitiveBlock).
+///
+/// The strings are kept as the raw bytes of the decoded message; they are
+/// validated once when the block is created and returned as `&str` without
+/// copying. In lossy mode, invalid UTF-8 is replaced with `U+FFFD` instead of
+/// rejecting the whole block (only the invalid strings are copied).
+#[derive(Clone, Default)]
+pub struct StringTable {
+    strings: Vec<Bytes>,
+    /// lossy versions of the invalid strings, sorted by index
+    replacements: Vec<(u32, Box<str>)>,
+}
+
+impl StringTable {
+    /// Validates the strings; fails on the first invalid one.
+    pub(crate) fn new(strings: Vec<Bytes>) -> Result<Self> {
+        for s in &strings {
+            if str::from_utf8(s).is_err() {
+                // only allocates for the error
+                String::from_utf8(s.to_vec())?;
+            }
+        }
+        Ok(Self {
+            strings,
+            replacements: Vec::new(),
+        })
+    }
+
+    /// Validates the strings; replaces invalid UTF-8 with `U+FFFD`.
+    pub(crate) fn new_lossy(strings: Vec<Bytes>) -> Self {
+        let replacements = strings
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| str::from_utf8(s).is_err())
+            .map(|(i, s)| (i as u32, String::from_utf8_lossy(s).into()))
+            .collect();
+        Self {
+            strings,
+            replacements,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns `true` when strings with invalid UTF-8 were replaced.
+    #[inline]
+    pub fn is_lossy(&self) -> bool {
+        !self.replacements.is_empty()
+    }
+
+    /// The string at the given index.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let bytes = self.strings.get(index)?;
+        if !self.replacements.is_empty() {
+            if let Ok(i) = self
+                .replacements
+                .binary_search_by_key(&index, |&(i, _)| i as usize)
+            {
+                return Some(&self.replacements[i].1);
+            }
+        }
+        // SAFETY: all strings without a replacement were validated in `new`
+        // or `new_lossy`, and the bytes are immutable.
+        Some(unsafe { str::from_utf8_unchecked(bytes) })
+    }
+
+    /// The raw bytes of the string at the given index (even if invalid).
+    #[inline]
+    pub fn get_bytes(&self, index: usize) -> Option<&[u8]> {
+        self.strings.get(index).map(|s| &s[..])
+    }
+
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            table: self,
+            range: 0..self.len(),
+        }
+    }
+
+    /// The raw strings, e.g. for encoding the block.
+    #[inline]
+    pub(crate) fn raw(&self) -> &[Bytes] {
+        &self.strings
+    }
+}
+
+impl From<Vec<String>> for StringTable {
+    #[inline]
+    fn from(strings: Vec<String>) -> Self {
+        Self {
+            strings: strings.into_iter().map(Bytes::from).collect(),
+            replacements: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for StringTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'l> IntoIterator for &'l StringTable {
+    type Item = &'l str;
+    type IntoIter = Iter<'l>;
+    #[inline]
+    fn into_iter(self) -> Iter<'l> {
+        self.iter()
+    }
+}
+
+/// Iterator over the strings of a [`StringTable`].
+#[derive(Clone)]
+pub struct Iter<'l> {
+    table: &'l StringTable,
+    range: std::ops::Range<usize>,
+}
+
+impl<'l> Iterator for Iter<'l> {
+    type Item = &'l str;
+    #[inline]
+    fn next(&mut self) -> Option<&'l str> {
+        self.table.get(self.range.next()?)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
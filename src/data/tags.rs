@@ -1,29 +1,32 @@
-use std::slice::Iter;
-use std::{iter::FusedIterator, ops::Deref};
+use std::iter::FusedIterator;
+
+use super::packed::{Packed, PackedIter};
+use super::string_table::StringTable;
 
 #[derive(Copy, Clone)]
-pub(super) struct TagFields<'l>(pub &'l [u32], pub &'l [u32]);
+pub(super) struct TagFields<'l>(pub Packed<'l, u32>, pub Packed<'l, u32>);
 
 #[derive(Copy, Clone)]
 pub(super) enum NodeTagFields<'l> {
-    Normal(&'l [u32], &'l [u32]),
-    Dense(&'l [i32]),
+    Normal(Packed<'l, u32>, Packed<'l, u32>),
+    Dense(Packed<'l, i32>),
 }
 
 #[derive(Clone)]
 enum TagIterFields<'l> {
-    Normal(Iter<'l, u32>, Iter<'l, u32>),
-    Dense(Iter<'l, i32>),
+    Normal(PackedIter<'l, u32>, PackedIter<'l, u32>),
+    Dense(PackedIter<'l, i32>),
 }
 
+#[derive(Clone)]
 pub struct Tags<'l> {
-    strings: &'l [String],
+    strings: &'l StringTable,
     iters: TagIterFields<'l>,
 }
 
 impl<'l> TagFields<'l> {
     #[inline]
-    pub fn iter_with_strings(self, strings: &'l [String]) -> Tags<'l> {
+    pub fn iter_with_strings(self, strings: &'l StringTable) -> Tags<'l> {
         Tags {
             strings,
             iters: TagIterFields::Normal(self.0.iter(), self.1.iter()),
@@ -33,7 +36,7 @@ impl<'l> TagFields<'l> {
 
 impl<'l> NodeTagFields<'l> {
     #[inline]
-    pub fn iter_with_strings(self, strings: &'l [String]) -> Tags<'l> {
+    pub fn iter_with_strings(self, strings: &'l StringTable) -> Tags<'l> {
         Tags {
             strings,
             iters: match self {
@@ -54,20 +57,16 @@ impl<'l> Iterator for Tags<'l> {
         let value_index;
         match self.iters {
             TagIterFields::Normal(ref mut keys, ref mut values) => {
-                key_index = keys.next().copied()? as usize;
-                value_index = values.next().copied()? as usize;
+                key_index = keys.next()? as usize;
+                value_index = values.next()? as usize;
             }
             TagIterFields::Dense(ref mut key_values) => {
-                key_index = key_values.next().copied()? as usize;
-                value_index = key_values.next().copied()? as usize;
+                key_index = key_values.next()? as usize;
+                value_index = key_values.next()? as usize;
             }
         }
-        let key = self.strings.get(key_index).map(Deref::deref).unwrap_or("");
-        let value = self
-            .strings
-            .get(value_index)
-            .map(Deref::deref)
-            .unwrap_or("");
+        let key = self.strings.get(key_index).unwrap_or("");
+        let value = self.strings.get(value_index).unwrap_or("");
         Some((key, value))
     }
 
@@ -90,3 +89,11 @@ impl<'l> Iterator for Tags<'l> {
     }
 }
 impl<'l> FusedIterator for Tags<'l> {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Tags<'_> {
+    /// Serializes the tags as a sequence of key-value pairs.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.clone())
+    }
+}
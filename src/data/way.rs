@@ -4,41 +4,75 @@ use osm_pbf_proto::osmformat::Way as PbfWay;
 
 use super::{
     node::NodeId,
+    packed::{Packed, PackedIter},
+    string_table::StringTable,
     tags::{TagFields, Tags},
-    Meta,
+    Meta, Offset, OwnedMeta,
 };
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct WayId(pub i64);
 
 pub struct Way<'l> {
     pub id: WayId,
 
-    strings: &'l [String],
+    strings: &'l StringTable,
 
-    refs: &'l [i64],
+    refs: Packed<'l, i64>,
+    lat: Packed<'l, i64>,
+    lon: Packed<'l, i64>,
+    offset: Offset,
 
     tags: TagFields<'l>,
-    meta: Meta,
+    meta: Meta<'l>,
 }
 
-impl Deref for Way<'_> {
-    type Target = Meta;
+impl<'l> Deref for Way<'l> {
+    type Target = Meta<'l>;
     #[inline]
-    fn deref(&self) -> &Meta {
+    fn deref(&self) -> &Meta<'l> {
         &self.meta
     }
 }
 
 impl<'l> Way<'l> {
     #[inline]
-    pub(crate) fn from_pbf(w: &'l PbfWay, strings: &'l [String]) -> Self {
+    pub(super) fn from_pbf(w: &'l PbfWay, offset: &Offset, strings: &'l StringTable) -> Self {
+        Self::from_parts(
+            w.id(),
+            [
+                Packed::Slice(&w.refs),
+                Packed::Slice(&w.lat),
+                Packed::Slice(&w.lon),
+            ],
+            offset,
+            TagFields(Packed::Slice(&w.keys), Packed::Slice(&w.vals)),
+            Meta::from_info(&w.info, offset, strings),
+            strings,
+        )
+    }
+
+    /// The delta coded `[refs, lat, lon]` fields.
+    #[inline]
+    pub(super) fn from_parts(
+        id: i64,
+        [refs, lat, lon]: [Packed<'l, i64>; 3],
+        offset: &Offset,
+        tags: TagFields<'l>,
+        meta: Meta<'l>,
+        strings: &'l StringTable,
+    ) -> Self {
         Self {
-            id: WayId(w.id()),
+            id: WayId(id),
             strings,
-            refs: &w.refs,
-            tags: TagFields(&w.keys, &w.vals),
-            meta: Meta::from_info(&w.info),
+            refs,
+            lat,
+            lon,
+            offset: *offset,
+            tags,
+            meta,
         }
     }
 
@@ -50,14 +84,109 @@ impl<'l> Way<'l> {
         }
     }
 
+    /// Returns `true` when the way contains the locations of its nodes
+    /// (`LocationsOnWays`).
+    #[inline]
+    pub fn has_locations(&self) -> bool {
+        if self.lat.is_empty() {
+            return false;
+        }
+        let len = self.refs.len();
+        self.lat.len() == len && self.lon.len() == len
+    }
+
+    /// The locations of the nodes in nanodegrees as `(nano_lat, nano_lon)`
+    /// in the same order as [`Way::refs`].
+    ///
+    /// Only available in files with the feature `LocationsOnWays`, empty otherwise.
+    #[inline]
+    pub fn locations(&self) -> Locations<'l> {
+        let (lat, lon) = if self.has_locations() {
+            (self.lat, self.lon)
+        } else {
+            (Packed::default(), Packed::default())
+        };
+        Locations {
+            lat: lat.iter(),
+            lon: lon.iter(),
+            current: (0, 0),
+            offset: self.offset,
+        }
+    }
+
+    /// The referenced nodes together with their locations, see [`Way::locations`].
+    #[inline]
+    pub fn refs_with_locations(&self) -> impl Iterator<Item = (NodeId, (i64, i64))> + 'l {
+        self.refs().zip(self.locations())
+    }
+
     #[inline]
     pub fn tags(&self) -> Tags<'l> {
         self.tags.iter_with_strings(self.strings)
     }
+
+    pub fn to_owned(&self) -> OwnedWay {
+        OwnedWay {
+            id: self.id,
+            refs: self.refs().collect(),
+            locations: self.locations().collect(),
+            tags: self
+                .tags()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            meta: self.meta.to_owned(),
+        }
+    }
+}
+
+/// Serialized like [`OwnedWay`].
+#[cfg(feature = "serde")]
+impl serde::Serialize for Way<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+        let mut state = serializer.serialize_struct("OwnedWay", 5)?;
+        state.serialize_field("id", &self.id)?;
+        state.serialize_field("refs", &self.refs())?;
+        state.serialize_field("locations", &self.locations())?;
+        state.serialize_field("tags", &self.tags())?;
+        state.serialize_field("meta", &self.meta)?;
+        state.end()
+    }
+}
+
+/// Owned version of [`Way`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OwnedWay {
+    pub id: WayId,
+    pub refs: Vec<NodeId>,
+    /// Locations of the nodes as `(nano_lat, nano_lon)`, empty when not available.
+    ///
+    /// Always serialized, but optional when deserializing older input.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub locations: Vec<(i64, i64)>,
+    pub tags: Vec<(String, String)>,
+    pub meta: OwnedMeta,
 }
 
+impl Deref for OwnedWay {
+    type Target = OwnedMeta;
+    #[inline]
+    fn deref(&self) -> &OwnedMeta {
+        &self.meta
+    }
+}
+
+impl From<&Way<'_>> for OwnedWay {
+    #[inline]
+    fn from(way: &Way<'_>) -> Self {
+        way.to_owned()
+    }
+}
+
+#[derive(Clone)]
 pub struct Refs<'l> {
-    iter: std::slice::Iter<'l, i64>,
+    iter: PackedIter<'l, i64>,
     current: i64,
 }
 
@@ -83,7 +212,7 @@ impl Iterator for Refs<'_> {
     type Item = NodeId;
     #[inline]
     fn next(&mut self) -> Option<NodeId> {
-        self.current += self.iter.next()?;
+        self.current = self.current.wrapping_add(self.iter.next()?);
         Some(NodeId(self.current))
     }
 
@@ -99,3 +228,99 @@ impl Iterator for Refs<'_> {
 }
 
 impl FusedIterator for Refs<'_> {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Refs<'_> {
+    /// Serializes the remaining node ids as a sequence.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.clone())
+    }
+}
+
+/// Iterator over the node locations of a way, see [`Way::locations`].
+#[derive(Clone)]
+pub struct Locations<'l> {
+    lat: PackedIter<'l, i64>,
+    lon: PackedIter<'l, i64>,
+    current: (i64, i64),
+    offset: Offset,
+}
+
+impl Iterator for Locations<'_> {
+    type Item = (i64, i64);
+    #[inline]
+    fn next(&mut self) -> Option<(i64, i64)> {
+        self.current.0 = self.current.0.wrapping_add(self.lat.next()?);
+        self.current.1 = self.current.1.wrapping_add(self.lon.next()?);
+        let (lat, lon) = self.current;
+        let granularity = self.offset.granularity as i64;
+        Some((
+            lat.wrapping_mul(granularity).wrapping_add(self.offset.lat),
+            lon.wrapping_mul(granularity).wrapping_add(self.offset.lon),
+        ))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.lat.size_hint()
+    }
+}
+
+impl FusedIterator for Locations<'_> {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Locations<'_> {
+    /// Serializes the remaining locations as a sequence.
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.clone())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use crate::data::{
+        builder::PrimitiveBlockBuilder,
+        primitive::{OwnedPrimitive, Primitive},
+    };
+
+    fn way(locations: Vec<(i64, i64)>) -> OwnedWay {
+        OwnedWay {
+            id: WayId(7),
+            refs: vec![NodeId(1), NodeId(2)],
+            locations,
+            tags: vec![("highway".to_string(), "path".to_string())],
+            meta: OwnedMeta {
+                user: "foo".to_string(),
+                ..OwnedMeta::default()
+            },
+        }
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        for way in [way(Vec::new()), way(vec![(100, 200), (-300, 400)])] {
+            let bytes = bincode::serialize(&way).unwrap();
+            assert_eq!(bincode::deserialize::<OwnedWay>(&bytes).unwrap(), way);
+
+            // the borrowed view uses the same layout
+            let mut builder = PrimitiveBlockBuilder::new();
+            builder.add_owned(&OwnedPrimitive::Way(way.clone()));
+            let block = builder.build();
+            let Some(Primitive::Way(borrowed)) = block.primitives().next() else {
+                panic!("expected a way");
+            };
+            assert_eq!(bincode::serialize(&borrowed).unwrap(), bytes);
+        }
+    }
+
+    #[test]
+    fn deserialize_without_locations() {
+        let json = r#"{"id":7,"refs":[1,2],"tags":[["highway","path"]],"meta":{"version":0,"timestamp":0,"changeset":0,"uid":0,"user":"foo","visible":true}}"#;
+        let way: OwnedWay = serde_json::from_str(json).unwrap();
+        assert_eq!(way, self::way(Vec::new()));
+        assert!(serde_json::to_string(&way)
+            .unwrap()
+            .contains(r#""locations":[]"#));
+    }
+}
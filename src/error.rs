@@ -14,6 +14,14 @@ pub enum Error {
     #[error(transparent)]
     Utf8Error(#[from] FromUtf8Error),
 
+    #[cfg(feature = "xml")]
+    #[error(transparent)]
+    XmlError(#[from] quick_xml::Error),
+
+    #[cfg(feature = "xml")]
+    #[error("Invalid OSM XML: {0}")]
+    InvalidXml(String),
+
     // The length of the BlobHeader [..] must be less than 64 KiB.
     // https://wiki.openstreetmap.org/wiki/PBF_Format
     #[error("Invalid Format: The size of the `BlobHeader` is to large")]
@@ -27,6 +35,24 @@ pub enum Error {
     #[error("The encoding of the Blob is not supported")]
     UnsupportedEncoding,
 
+    #[error("The required feature {0} is not supported")]
+    UnsupportedFeature(String),
+
+    #[error("The file is not sorted by type and id")]
+    NotSortedByTypeThenId,
+
+    #[error("Invalid tag filter `{0}`")]
+    InvalidTagFilter(String),
+
+    #[error("Invalid OPL: {0}")]
+    InvalidOpl(String),
+
+    #[error("Invalid o5m: {0}")]
+    InvalidO5m(String),
+
+    #[error("Invalid PBF data: {0}")]
+    InvalidPbf(String),
+
     #[error("Unexpected Blob-Type {0}")]
     UnexpectedBlobType(String),
 }
@@ -0,0 +1,261 @@
+//! Regional extracts by bounding box or (multi)polygon.
+
+use std::io::{self, Write};
+
+use crate::area::{Area, Polygon};
+use crate::blob::Blobs;
+use crate::data::{
+    node::NodeId,
+    primitive::{Primitive, PrimitiveType},
+    relation::{Member, RelationId},
+    way::WayId,
+};
+use crate::error::Result;
+use crate::header::BoundingBox;
+use crate::id_set::{NodeIdSet, RelationIdSet, WayIdSet};
+use crate::location::Location;
+use crate::writer::PbfWriter;
+
+/// The region of an extract.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Region {
+    BoundingBox(BoundingBox),
+    /// A multipolygon; a location is inside when it is inside any of the polygons.
+    Polygons(Vec<Polygon>),
+}
+
+impl Region {
+    /// Returns `true` when the location (in nanodegrees) is inside the region.
+    pub fn contains(&self, nano_lat: i64, nano_lon: i64) -> bool {
+        match self {
+            Region::BoundingBox(bbox) => bbox.contains(nano_lat, nano_lon),
+            Region::Polygons(polygons) => {
+                let location = Location::new(nano_lat, nano_lon);
+                polygons.iter().any(|p| p.contains(location))
+            }
+        }
+    }
+
+    /// The bounding box of the region, e.g. for the header of the extract.
+    pub fn bbox(&self) -> Option<BoundingBox> {
+        match self {
+            Region::BoundingBox(bbox) => Some(*bbox),
+            Region::Polygons(polygons) => {
+                let mut locations = polygons.iter().flat_map(|p| &p.outer.locations);
+                let first = locations.next()?;
+                let mut bbox = BoundingBox {
+                    left: first.nano_lon,
+                    right: first.nano_lon,
+                    top: first.nano_lat,
+                    bottom: first.nano_lat,
+                };
+                for l in locations {
+                    bbox.left = bbox.left.min(l.nano_lon);
+                    bbox.right = bbox.right.max(l.nano_lon);
+                    bbox.top = bbox.top.max(l.nano_lat);
+                    bbox.bottom = bbox.bottom.min(l.nano_lat);
+                }
+                Some(bbox)
+            }
+        }
+    }
+}
+
+impl From<BoundingBox> for Region {
+    #[inline]
+    fn from(bbox: BoundingBox) -> Self {
+        Region::BoundingBox(bbox)
+    }
+}
+
+impl From<&Area> for Region {
+    #[inline]
+    fn from(area: &Area) -> Self {
+        Region::Polygons(area.polygons.clone())
+    }
+}
+
+/// How ways and relations crossing the border of the region are handled
+/// (like the strategies of `osmium extract`).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    /// Nodes inside the region, ways with at least one of these nodes and
+    /// relations with at least one of these nodes, ways or relations as
+    /// members. Ways crossing the border are incomplete.
+    Simple,
+    /// Like `Simple`, but with all nodes of the ways (two passes).
+    #[default]
+    CompleteWays,
+    /// Like `CompleteWays`, but with all ways (and their nodes) of the
+    /// multipolygon relations (three passes).
+    Smart,
+}
+
+/// Number of elements written by [`Extract::run`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtractStats {
+    pub nodes: u64,
+    pub ways: u64,
+    pub relations: u64,
+}
+
+struct StoredRelation {
+    id: RelationId,
+    members: Vec<(PrimitiveType, i64)>,
+    multipolygon: bool,
+}
+
+/// Cuts a regional extract from a file sorted by type.
+pub struct Extract {
+    region: Region,
+    strategy: Strategy,
+}
+
+impl Extract {
+    #[inline]
+    pub fn new(region: impl Into<Region>) -> Self {
+        Self {
+            region: region.into(),
+            strategy: Strategy::default(),
+        }
+    }
+
+    #[inline]
+    pub fn with_strategy(mut self, strategy: Strategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    #[inline]
+    pub fn region(&self) -> &Region {
+        &self.region
+    }
+
+    #[inline]
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
+    /// Reads the blobs (multiple times, depending on the strategy) and
+    /// writes the elements of the extract.
+    ///
+    /// The nodes must precede the ways in the file, as in files sorted by
+    /// type and id.
+    pub fn run<R, W>(&self, blobs: &mut Blobs<R>, writer: &mut PbfWriter<W>) -> Result<ExtractStats>
+    where
+        R: io::BufRead + io::Seek,
+        W: Write,
+    {
+        let complete_ways = self.strategy != Strategy::Simple;
+        let mut nodes = NodeIdSet::new();
+        let mut extra_nodes = NodeIdSet::new();
+        let mut ways = WayIdSet::new();
+        let mut relations = Vec::new();
+
+        // pass 1: nodes in the region and ways referencing them
+        blobs.rewind()?;
+        for blob in &mut *blobs {
+            let block = blob?.decode()?;
+            for primitive in block.primitives() {
+                match primitive {
+                    Primitive::Node(n) if self.region.contains(n.nano_lat, n.nano_lon) => {
+                        nodes.insert(n.id);
+                    }
+                    Primitive::Way(w) if w.refs().any(|id| nodes.contains(id)) => {
+                        ways.insert(w.id);
+                        if complete_ways {
+                            extra_nodes.extend(w.refs());
+                        }
+                    }
+                    Primitive::Relation(r) => relations.push(StoredRelation {
+                        id: r.id,
+                        members: r.members().map(member_key).collect(),
+                        multipolygon: r.tags().any(|t| t == ("type", "multipolygon")),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+
+        // relations with members in the extract, including parent relations
+        let mut selected = RelationIdSet::new();
+        loop {
+            let count = selected.len();
+            for r in &relations {
+                if !selected.contains(r.id)
+                    && r.members.iter().any(|&(t, id)| match t {
+                        PrimitiveType::NODE => nodes.contains(NodeId(id)),
+                        PrimitiveType::WAY => ways.contains(WayId(id)),
+                        _ => selected.contains(RelationId(id)),
+                    })
+                {
+                    selected.insert(r.id);
+                }
+            }
+            if selected.len() == count {
+                break;
+            }
+        }
+
+        // pass 2 (smart): all ways of the multipolygon relations with their nodes
+        if self.strategy == Strategy::Smart {
+            let mut missing_ways = WayIdSet::new();
+            for r in relations.iter().filter(|r| r.multipolygon) {
+                if selected.contains(r.id) {
+                    missing_ways.extend(
+                        r.members
+                            .iter()
+                            .filter(|&&(t, id)| {
+                                t == PrimitiveType::WAY && !ways.contains(WayId(id))
+                            })
+                            .map(|&(_, id)| WayId(id)),
+                    );
+                }
+            }
+            if !missing_ways.is_empty() {
+                blobs.rewind()?;
+                for blob in &mut *blobs {
+                    let block = blob?.decode()?;
+                    for primitive in block.primitives().types(PrimitiveType::WAY) {
+                        if let Primitive::Way(w) = primitive {
+                            if missing_ways.contains(w.id) {
+                                extra_nodes.extend(w.refs());
+                            }
+                        }
+                    }
+                }
+                ways.extend(missing_ways.iter());
+            }
+        }
+        drop(relations);
+
+        // last pass: write the selected elements
+        let mut stats = ExtractStats::default();
+        blobs.rewind()?;
+        for blob in &mut *blobs {
+            let block = blob?.decode()?;
+            for primitive in block.primitives() {
+                let counter = match &primitive {
+                    Primitive::Node(n) if nodes.contains(n.id) || extra_nodes.contains(n.id) => {
+                        &mut stats.nodes
+                    }
+                    Primitive::Way(w) if ways.contains(w.id) => &mut stats.ways,
+                    Primitive::Relation(r) if selected.contains(r.id) => &mut stats.relations,
+                    _ => continue,
+                };
+                writer.write_primitive(&primitive)?;
+                *counter += 1;
+            }
+        }
+        Ok(stats)
+    }
+}
+
+#[inline]
+fn member_key(member: Member<'_>) -> (PrimitiveType, i64) {
+    match member {
+        Member::Node(id, _) => (PrimitiveType::NODE, id.0),
+        Member::Way(id, _) => (PrimitiveType::WAY, id.0),
+        Member::Relation(id, _) => (PrimitiveType::RELATION, id.0),
+    }
+}
@@ -0,0 +1,281 @@
+//! Conversions to [`geo_types`] and a streaming GeoJSON writer.
+
+use std::io::Write;
+
+use geo_types::{Coord, Geometry, LineString, MultiPolygon, Point, Polygon as GeoPolygon};
+
+use crate::area::{Area, AreaId, Polygon, Ring};
+use crate::data::{
+    node::{Node, OwnedNode},
+    way::Way,
+};
+use crate::error::Result;
+use crate::location::{Location, WayGeometry};
+
+impl From<Location> for Coord<f64> {
+    #[inline]
+    fn from(location: Location) -> Self {
+        Coord {
+            x: location.lon(),
+            y: location.lat(),
+        }
+    }
+}
+
+impl From<Location> for Point<f64> {
+    #[inline]
+    fn from(location: Location) -> Self {
+        Point(location.into())
+    }
+}
+
+impl From<&Node<'_>> for Point<f64> {
+    #[inline]
+    fn from(node: &Node<'_>) -> Self {
+        Point::new(node.lon(), node.lat())
+    }
+}
+
+impl From<&OwnedNode> for Point<f64> {
+    #[inline]
+    fn from(node: &OwnedNode) -> Self {
+        Point::new(node.lon(), node.lat())
+    }
+}
+
+impl From<&WayGeometry> for LineString<f64> {
+    #[inline]
+    fn from(geometry: &WayGeometry) -> Self {
+        geometry
+            .locations
+            .iter()
+            .copied()
+            .map(Coord::from)
+            .collect()
+    }
+}
+
+impl WayGeometry {
+    /// The resolved locations as a line string.
+    #[inline]
+    pub fn to_line_string(&self) -> LineString<f64> {
+        self.into()
+    }
+
+    /// The resolved locations as a polygon without holes, `None` when the
+    /// way is not closed.
+    pub fn to_polygon(&self) -> Option<GeoPolygon<f64>> {
+        if !self.is_closed() {
+            return None;
+        }
+        Some(GeoPolygon::new(self.to_line_string(), Vec::new()))
+    }
+}
+
+impl From<&Ring> for LineString<f64> {
+    #[inline]
+    fn from(ring: &Ring) -> Self {
+        ring.locations.iter().copied().map(Coord::from).collect()
+    }
+}
+
+impl From<&Polygon> for GeoPolygon<f64> {
+    fn from(polygon: &Polygon) -> Self {
+        GeoPolygon::new(
+            (&polygon.outer).into(),
+            polygon.inners.iter().map(LineString::from).collect(),
+        )
+    }
+}
+
+impl From<&Area> for MultiPolygon<f64> {
+    #[inline]
+    fn from(area: &Area) -> Self {
+        area.polygons.iter().map(GeoPolygon::from).collect()
+    }
+}
+
+/// Writes a GeoJSON `FeatureCollection`, one feature at a time.
+///
+/// The tags of the elements are written as the properties of the features,
+/// the ids as `n<id>`, `w<id>` or `r<id>`.
+pub struct GeoJsonWriter<W: Write> {
+    write: W,
+    empty: bool,
+}
+
+impl<W: Write> GeoJsonWriter<W> {
+    /// Creates the writer and writes the start of the collection.
+    pub fn new(mut write: W) -> Result<Self> {
+        write.write_all(br#"{"type":"FeatureCollection","features":["#)?;
+        Ok(Self { write, empty: true })
+    }
+
+    /// Writes a feature with the given geometry and properties.
+    pub fn write_feature<K, V>(
+        &mut self,
+        id: Option<&str>,
+        geometry: &Geometry<f64>,
+        properties: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let w = &mut self.write;
+        if !self.empty {
+            w.write_all(b",")?;
+        }
+        self.empty = false;
+        w.write_all(br#"{"type":"Feature","#)?;
+        if let Some(id) = id {
+            w.write_all(br#""id":"#)?;
+            write_string(w, id)?;
+            w.write_all(b",")?;
+        }
+        w.write_all(br#""geometry":"#)?;
+        write_geometry(w, geometry)?;
+        w.write_all(br#","properties":{"#)?;
+        for (i, (k, v)) in properties.into_iter().enumerate() {
+            if i > 0 {
+                w.write_all(b",")?;
+            }
+            write_string(w, k.as_ref())?;
+            w.write_all(b":")?;
+            write_string(w, v.as_ref())?;
+        }
+        w.write_all(b"}}")?;
+        Ok(())
+    }
+
+    /// Writes the node as a `Point`.
+    pub fn write_node(&mut self, node: &Node<'_>) -> Result<()> {
+        let id = format!("n{}", node.id.0);
+        self.write_feature(Some(&id), &Point::from(node).into(), node.tags())
+    }
+
+    /// Writes the way as a `LineString` with the resolved locations.
+    pub fn write_way(&mut self, way: &Way<'_>, geometry: &WayGeometry) -> Result<()> {
+        let id = format!("w{}", way.id.0);
+        self.write_feature(Some(&id), &geometry.to_line_string().into(), way.tags())
+    }
+
+    /// Writes the area as a `MultiPolygon`.
+    pub fn write_area(&mut self, area: &Area) -> Result<()> {
+        let id = match area.id {
+            AreaId::Way(id) => format!("w{}", id.0),
+            AreaId::Relation(id) => format!("r{}", id.0),
+        };
+        let tags = area.tags.iter().map(|(k, v)| (k, v));
+        self.write_feature(Some(&id), &MultiPolygon::from(area).into(), tags)
+    }
+
+    /// Writes the end of the collection and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write.write_all(b"]}")?;
+        self.write.flush()?;
+        Ok(self.write)
+    }
+}
+
+fn write_string(w: &mut dyn Write, s: &str) -> Result<()> {
+    w.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let escaped: &[u8] = match c {
+            '"' => b"\\\"",
+            '\\' => b"\\\\",
+            '\n' => b"\\n",
+            '\r' => b"\\r",
+            '\t' => b"\\t",
+            c if (c as u32) < 0x20 => {
+                w.write_all(&s.as_bytes()[start..i])?;
+                write!(w, "\\u{:04x}", c as u32)?;
+                start = i + 1;
+                continue;
+            }
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..i])?;
+        w.write_all(escaped)?;
+        start = i + 1;
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")?;
+    Ok(())
+}
+
+fn write_coord(w: &mut dyn Write, c: Coord<f64>) -> Result<()> {
+    write!(w, "[{},{}]", c.x, c.y)?;
+    Ok(())
+}
+
+fn write_coords(w: &mut dyn Write, coords: impl IntoIterator<Item = Coord<f64>>) -> Result<()> {
+    w.write_all(b"[")?;
+    for (i, c) in coords.into_iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        write_coord(w, c)?;
+    }
+    w.write_all(b"]")?;
+    Ok(())
+}
+
+fn write_polygon_coords(w: &mut dyn Write, polygon: &GeoPolygon<f64>) -> Result<()> {
+    w.write_all(b"[")?;
+    write_coords(w, polygon.exterior().0.iter().copied())?;
+    for ring in polygon.interiors() {
+        w.write_all(b",")?;
+        write_coords(w, ring.0.iter().copied())?;
+    }
+    w.write_all(b"]")?;
+    Ok(())
+}
+
+fn write_list<T>(
+    w: &mut dyn Write,
+    items: &[T],
+    mut f: impl FnMut(&mut dyn Write, &T) -> Result<()>,
+) -> Result<()> {
+    w.write_all(b"[")?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            w.write_all(b",")?;
+        }
+        f(w, item)?;
+    }
+    w.write_all(b"]")?;
+    Ok(())
+}
+
+fn write_geometry(w: &mut dyn Write, geometry: &Geometry<f64>) -> Result<()> {
+    let (kind, field) = match geometry {
+        Geometry::Point(_) => ("Point", "coordinates"),
+        Geometry::Line(_) | Geometry::LineString(_) => ("LineString", "coordinates"),
+        Geometry::Polygon(_) | Geometry::Rect(_) | Geometry::Triangle(_) => {
+            ("Polygon", "coordinates")
+        }
+        Geometry::MultiPoint(_) => ("MultiPoint", "coordinates"),
+        Geometry::MultiLineString(_) => ("MultiLineString", "coordinates"),
+        Geometry::MultiPolygon(_) => ("MultiPolygon", "coordinates"),
+        Geometry::GeometryCollection(_) => ("GeometryCollection", "geometries"),
+    };
+    write!(w, r#"{{"type":"{kind}","{field}":"#)?;
+    match geometry {
+        Geometry::Point(p) => write_coord(w, p.0)?,
+        Geometry::Line(l) => write_coords(w, [l.start, l.end])?,
+        Geometry::LineString(l) => write_coords(w, l.0.iter().copied())?,
+        Geometry::Polygon(p) => write_polygon_coords(w, p)?,
+        Geometry::Rect(r) => write_polygon_coords(w, &r.to_polygon())?,
+        Geometry::Triangle(t) => write_polygon_coords(w, &t.to_polygon())?,
+        Geometry::MultiPoint(m) => write_coords(w, m.0.iter().map(|p| p.0))?,
+        Geometry::MultiLineString(m) => {
+            write_list(w, &m.0, |w, l| write_coords(w, l.0.iter().copied()))?
+        }
+        Geometry::MultiPolygon(m) => write_list(w, &m.0, |w, p| write_polygon_coords(w, p))?,
+        Geometry::GeometryCollection(c) => write_list(w, &c.0, |w, g| write_geometry(w, g))?,
+    }
+    w.write_all(b"}")?;
+    Ok(())
+}
@@ -1,10 +1,15 @@
-use std::ops::Deref;
+use std::ops::{Deref, DerefMut};
 
-pub use osm_pbf_proto::osmformat::HeaderBlock as PbfHeaderBlock;
+use bitflags::bitflags;
+pub use osm_pbf_proto::osmformat::{HeaderBBox as PbfHeaderBBox, HeaderBlock as PbfHeaderBlock};
 
-use crate::{blob::Block, error::Result};
+use crate::{
+    blob::Block,
+    error::{Error, Result},
+};
 
 // REQUIRED FEATURES
+pub const OSM_SCHEMA_V06: &str = "OsmSchema-V0.6";
 pub const DENSE_NODES: &str = "DenseNodes";
 pub const HISTORICAL_INFORMATION: &str = "HistoricalInformation";
 
@@ -14,10 +19,271 @@ pub const SORT_TYPE_THEN_ID: &str = "Sort.Type_then_ID";
 pub const SORT_GEOGRAPHIC: &str = "Sort.Geographic";
 pub const LOCATIONS_ON_WAYS: &str = "LocationsOnWays";
 
+bitflags! {
+    /// Features a reader must support to read the file.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct RequiredFeatures: u32 {
+        const OSM_SCHEMA_V06 = 1;
+        const DENSE_NODES = 2;
+        const HISTORICAL_INFORMATION = 4;
+    }
+}
+
+bitflags! {
+    /// Features that describe the content of the file.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct OptionalFeatures: u32 {
+        const HAS_METADATA = 1;
+        const SORT_TYPE_THEN_ID = 2;
+        const SORT_GEOGRAPHIC = 4;
+        const LOCATIONS_ON_WAYS = 8;
+    }
+}
+
+const REQUIRED_FEATURE_NAMES: &[(RequiredFeatures, &str)] = &[
+    (RequiredFeatures::OSM_SCHEMA_V06, OSM_SCHEMA_V06),
+    (RequiredFeatures::DENSE_NODES, DENSE_NODES),
+    (
+        RequiredFeatures::HISTORICAL_INFORMATION,
+        HISTORICAL_INFORMATION,
+    ),
+];
+
+const OPTIONAL_FEATURE_NAMES: &[(OptionalFeatures, &str)] = &[
+    (OptionalFeatures::HAS_METADATA, HAS_METADATA),
+    (OptionalFeatures::SORT_TYPE_THEN_ID, SORT_TYPE_THEN_ID),
+    (OptionalFeatures::SORT_GEOGRAPHIC, SORT_GEOGRAPHIC),
+    (OptionalFeatures::LOCATIONS_ON_WAYS, LOCATIONS_ON_WAYS),
+];
+
+macro_rules! impl_feature_names {
+    ($features:ident, $names:ident) => {
+        impl $features {
+            /// Returns the flag for the given feature-name.
+            pub fn from_feature_name(name: &str) -> Option<Self> {
+                $names.iter().find(|(_, n)| *n == name).map(|(f, _)| *f)
+            }
+
+            /// Returns the names of all features in this set.
+            pub fn feature_names(self) -> impl Iterator<Item = &'static str> {
+                $names
+                    .iter()
+                    .filter(move |(f, _)| self.contains(*f))
+                    .map(|(_, n)| *n)
+            }
+        }
+    };
+}
+
+impl_feature_names!(RequiredFeatures, REQUIRED_FEATURE_NAMES);
+impl_feature_names!(OptionalFeatures, OPTIONAL_FEATURE_NAMES);
+
+/// Bounding box in nanodegrees.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BoundingBox {
+    pub left: i64,
+    pub right: i64,
+    pub top: i64,
+    pub bottom: i64,
+}
+
+impl BoundingBox {
+    #[inline]
+    fn from_pbf(bbox: &PbfHeaderBBox) -> Self {
+        Self {
+            left: bbox.left(),
+            right: bbox.right(),
+            top: bbox.top(),
+            bottom: bbox.bottom(),
+        }
+    }
+
+    #[inline]
+    fn to_pbf(self) -> PbfHeaderBBox {
+        let mut bbox = PbfHeaderBBox::new();
+        bbox.set_left(self.left);
+        bbox.set_right(self.right);
+        bbox.set_top(self.top);
+        bbox.set_bottom(self.bottom);
+        bbox
+    }
+
+    /// Returns `true` when the location (in nanodegrees) is inside this box.
+    #[inline]
+    pub fn contains(&self, nano_lat: i64, nano_lon: i64) -> bool {
+        self.bottom <= nano_lat
+            && nano_lat <= self.top
+            && self.left <= nano_lon
+            && nano_lon <= self.right
+    }
+}
+
+#[derive(Clone)]
 pub struct HeaderBlock {
     pbf: PbfHeaderBlock,
 }
 
+impl HeaderBlock {
+    /// Creates a new header requiring the features `OsmSchema-V0.6` and `DenseNodes`.
+    pub fn new() -> Self {
+        let mut header = Self {
+            pbf: PbfHeaderBlock::new(),
+        };
+        header.add_required_features(
+            RequiredFeatures::OSM_SCHEMA_V06 | RequiredFeatures::DENSE_NODES,
+        );
+        header.set_writing_program(concat!("osm-pbf-reader/", env!("CARGO_PKG_VERSION")));
+        header
+    }
+}
+
+impl HeaderBlock {
+    #[inline]
+    pub fn bbox(&self) -> Option<BoundingBox> {
+        self.pbf.bbox.as_ref().map(BoundingBox::from_pbf)
+    }
+
+    #[inline]
+    pub fn set_bbox(&mut self, bbox: Option<BoundingBox>) {
+        self.pbf.bbox = bbox.map(BoundingBox::to_pbf).into();
+    }
+
+    /// The known features in `required_features`.
+    pub fn required_features(&self) -> RequiredFeatures {
+        self.pbf
+            .required_features
+            .iter()
+            .filter_map(|f| RequiredFeatures::from_feature_name(f))
+            .collect()
+    }
+
+    /// The entries in `required_features` that are not known by this crate.
+    pub fn unknown_required_features(&self) -> impl Iterator<Item = &str> {
+        self.pbf
+            .required_features
+            .iter()
+            .map(|f| &**f)
+            .filter(|f| RequiredFeatures::from_feature_name(f).is_none())
+    }
+
+    pub fn add_required_features(&mut self, features: RequiredFeatures) {
+        let missing = features.difference(self.required_features());
+        self.pbf
+            .required_features
+            .extend(missing.feature_names().map(Into::into));
+    }
+
+    /// Returns `true` for history files (`HistoricalInformation`), which may
+    /// contain multiple versions of an element, see [`crate::history`].
+    #[inline]
+    pub fn is_history(&self) -> bool {
+        self.required_features()
+            .contains(RequiredFeatures::HISTORICAL_INFORMATION)
+    }
+
+    /// The known features in `optional_features`.
+    pub fn optional_features(&self) -> OptionalFeatures {
+        self.pbf
+            .optional_features
+            .iter()
+            .filter_map(|f| OptionalFeatures::from_feature_name(f))
+            .collect()
+    }
+
+    /// The entries in `optional_features` that are not known by this crate
+    /// (for example `timestamp=...`).
+    pub fn unknown_optional_features(&self) -> impl Iterator<Item = &str> {
+        self.pbf
+            .optional_features
+            .iter()
+            .map(|f| &**f)
+            .filter(|f| OptionalFeatures::from_feature_name(f).is_none())
+    }
+
+    pub fn add_optional_features(&mut self, features: OptionalFeatures) {
+        let missing = features.difference(self.optional_features());
+        self.pbf
+            .optional_features
+            .extend(missing.feature_names().map(Into::into));
+    }
+
+    /// Adds an optional feature by name, also when it is not known by this crate.
+    pub fn add_optional_feature_name(&mut self, name: &str) {
+        if !self.pbf.optional_features.iter().any(|f| &**f == name) {
+            self.pbf.optional_features.push(name.into());
+        }
+    }
+
+    #[inline]
+    pub fn writing_program(&self) -> Option<&str> {
+        self.pbf.writingprogram.as_deref()
+    }
+
+    #[inline]
+    pub fn set_writing_program(&mut self, writing_program: &str) {
+        self.pbf.set_writingprogram(writing_program.into());
+    }
+
+    #[inline]
+    pub fn source(&self) -> Option<&str> {
+        self.pbf.source.as_deref()
+    }
+
+    #[inline]
+    pub fn set_source(&mut self, source: &str) {
+        self.pbf.set_source(source.into());
+    }
+
+    /// Replication timestamp in seconds since the unix epoch.
+    #[inline]
+    pub fn replication_timestamp(&self) -> Option<i64> {
+        self.pbf.osmosis_replication_timestamp
+    }
+
+    #[inline]
+    pub fn set_replication_timestamp(&mut self, timestamp: i64) {
+        self.pbf.set_osmosis_replication_timestamp(timestamp);
+    }
+
+    #[inline]
+    pub fn replication_sequence_number(&self) -> Option<i64> {
+        self.pbf.osmosis_replication_sequence_number
+    }
+
+    #[inline]
+    pub fn set_replication_sequence_number(&mut self, sequence_number: i64) {
+        self.pbf
+            .set_osmosis_replication_sequence_number(sequence_number);
+    }
+
+    #[inline]
+    pub fn replication_base_url(&self) -> Option<&str> {
+        self.pbf.osmosis_replication_base_url.as_deref()
+    }
+
+    #[inline]
+    pub fn set_replication_base_url(&mut self, base_url: &str) {
+        self.pbf.set_osmosis_replication_base_url(base_url.into());
+    }
+}
+
+impl Default for HeaderBlock {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<PbfHeaderBlock> for HeaderBlock {
+    #[inline]
+    fn from(pbf: PbfHeaderBlock) -> Self {
+        Self { pbf }
+    }
+}
+
 impl Deref for HeaderBlock {
     type Target = PbfHeaderBlock;
     #[inline]
@@ -26,12 +292,29 @@ impl Deref for HeaderBlock {
     }
 }
 
+impl DerefMut for HeaderBlock {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut PbfHeaderBlock {
+        &mut self.pbf
+    }
+}
+
 impl Block for HeaderBlock {
+    const BLOB_TYPE: &'static str = "OSMHeader";
+
     type Message = PbfHeaderBlock;
 
-    #[inline]
     fn from_message(pbf: PbfHeaderBlock) -> Result<Self> {
-        Ok(Self { pbf })
+        let header = Self { pbf };
+        if let Some(feature) = header.unknown_required_features().next() {
+            return Err(Error::UnsupportedFeature(feature.to_string()));
+        }
+        Ok(header)
+    }
+
+    #[inline]
+    fn to_message(&self) -> Result<PbfHeaderBlock> {
+        Ok(self.pbf.clone())
     }
 }
 
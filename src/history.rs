@@ -0,0 +1,195 @@
+//! Support for history files, which contain all versions of the elements.
+//!
+//! The versions of an element follow each other in history files, sorted by
+//! version. Deleted versions have `visible=false`.
+
+use std::iter::{FusedIterator, Peekable};
+
+use crate::data::{
+    primitive::{OwnedPrimitive, OwnedPrimitives, PrimitiveType},
+    OSMDataBlob,
+};
+use crate::error::Result;
+
+/// All versions of an element, ordered as in the file (by version).
+///
+/// Contains at least one version.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct History {
+    versions: Vec<OwnedPrimitive>,
+}
+
+impl History {
+    #[inline]
+    pub fn new(first: OwnedPrimitive) -> Self {
+        Self {
+            versions: vec![first],
+        }
+    }
+
+    /// Returns `None` when `versions` is empty.
+    #[inline]
+    pub fn from_versions(versions: Vec<OwnedPrimitive>) -> Option<Self> {
+        (!versions.is_empty()).then_some(Self { versions })
+    }
+
+    /// Appends a later version of the element.
+    #[inline]
+    pub fn push(&mut self, version: OwnedPrimitive) {
+        self.versions.push(version);
+    }
+
+    #[inline]
+    pub fn versions(&self) -> &[OwnedPrimitive] {
+        &self.versions
+    }
+
+    #[inline]
+    pub fn into_versions(self) -> Vec<OwnedPrimitive> {
+        self.versions
+    }
+
+    #[inline]
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.versions[0].primitive_type()
+    }
+
+    /// The raw id of the element.
+    #[inline]
+    pub fn id(&self) -> i64 {
+        self.versions[0].id()
+    }
+
+    #[inline]
+    pub fn latest(&self) -> &OwnedPrimitive {
+        &self.versions[self.versions.len() - 1]
+    }
+
+    /// Returns `true` when the latest version is deleted (`visible=false`).
+    #[inline]
+    pub fn is_deleted(&self) -> bool {
+        !is_visible(self.latest())
+    }
+
+    /// The version that was current at the given time (in milliseconds since
+    /// the unix epoch), `None` when it did not exist yet or was deleted.
+    #[inline]
+    pub fn at(&self, timestamp: i64) -> Option<&OwnedPrimitive> {
+        Some(&self.versions[self.position_at(timestamp)?])
+    }
+
+    fn position_at(&self, timestamp: i64) -> Option<usize> {
+        let count = self
+            .versions
+            .iter()
+            .take_while(|v| v.meta().is_none_or(|m| m.timestamp <= timestamp))
+            .count();
+        let pos = count.checked_sub(1)?;
+        is_visible(&self.versions[pos]).then_some(pos)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for History {
+    /// Fails when there are no versions.
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(rename = "History")]
+        struct Versions {
+            versions: Vec<OwnedPrimitive>,
+        }
+        let Versions { versions } = Versions::deserialize(deserializer)?;
+        Self::from_versions(versions)
+            .ok_or_else(|| serde::de::Error::invalid_length(0, &"at least one version"))
+    }
+}
+
+#[inline]
+fn is_visible(element: &OwnedPrimitive) -> bool {
+    element.meta().is_none_or(|m| m.visible)
+}
+
+/// Groups consecutive versions of the same element.
+pub struct Histories<I: Iterator> {
+    elements: Peekable<I>,
+}
+
+impl<I: Iterator<Item = Result<OwnedPrimitive>>> Histories<I> {
+    #[inline]
+    pub fn new(elements: I) -> Self {
+        Self {
+            elements: elements.peekable(),
+        }
+    }
+
+    /// The state of the data at the given time (in milliseconds since the
+    /// unix epoch), see [`History::at`].
+    #[inline]
+    pub fn at(self, timestamp: i64) -> TimeSlice<I> {
+        TimeSlice {
+            histories: self,
+            timestamp,
+        }
+    }
+}
+
+impl<I> Histories<OwnedPrimitives<I>>
+where
+    I: Iterator<Item = Result<OSMDataBlob>>,
+{
+    /// Groups the versions in a sequence of data blobs, like [`Blobs`](crate::Blobs).
+    #[inline]
+    pub fn from_blobs(blobs: I) -> Self {
+        Self::new(OwnedPrimitives::new(blobs))
+    }
+}
+
+impl<I: Iterator<Item = Result<OwnedPrimitive>>> Iterator for Histories<I> {
+    type Item = Result<History>;
+
+    fn next(&mut self) -> Option<Result<History>> {
+        let first = match self.elements.next()? {
+            Ok(first) => first,
+            Err(e) => return Some(Err(e)),
+        };
+        let key = (first.primitive_type(), first.id());
+        let mut history = History::new(first);
+        while let Some(Ok(next)) = self.elements.peek() {
+            if (next.primitive_type(), next.id()) != key {
+                break;
+            }
+            history
+                .versions
+                .extend(self.elements.next().and_then(Result::ok));
+        }
+        Some(Ok(history))
+    }
+}
+
+impl<I: FusedIterator<Item = Result<OwnedPrimitive>>> FusedIterator for Histories<I> {}
+
+/// Iterator over the elements as they were at a given time, see [`Histories::at`].
+pub struct TimeSlice<I: Iterator> {
+    histories: Histories<I>,
+    timestamp: i64,
+}
+
+impl<I: Iterator<Item = Result<OwnedPrimitive>>> Iterator for TimeSlice<I> {
+    type Item = Result<OwnedPrimitive>;
+
+    fn next(&mut self) -> Option<Result<OwnedPrimitive>> {
+        loop {
+            let mut history = match self.histories.next()? {
+                Ok(history) => history,
+                Err(e) => return Some(Err(e)),
+            };
+            let Some(pos) = history.position_at(self.timestamp) else {
+                continue;
+            };
+            return Some(Ok(history.versions.swap_remove(pos)));
+        }
+    }
+}
+
+impl<I: FusedIterator<Item = Result<OwnedPrimitive>>> FusedIterator for TimeSlice<I> {}
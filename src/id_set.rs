@@ -0,0 +1,145 @@
+//! Compact sets of element ids.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::data::{node::NodeId, relation::RelationId, way::WayId, ElementId};
+
+/// Number of ids per chunk.
+const CHUNK_BITS: u32 = 16;
+const CHUNK_WORDS: usize = (1 << CHUNK_BITS) / 64;
+
+type Chunk = Box<[u64; CHUNK_WORDS]>;
+
+/// A set of element ids, stored as a bitmap in chunks of 65536 ids (8 KiB).
+///
+/// Much more compact than a `HashSet` for the clustered ids of real data:
+/// a set of all nodes of the planet needs about 1.5 GiB.
+pub struct IdSet<I> {
+    chunks: HashMap<i64, Chunk>,
+    len: usize,
+    _marker: PhantomData<I>,
+}
+
+pub type NodeIdSet = IdSet<NodeId>;
+pub type WayIdSet = IdSet<WayId>;
+pub type RelationIdSet = IdSet<RelationId>;
+
+#[inline]
+fn split(id: i64) -> (i64, usize, u64) {
+    let bit = id.rem_euclid(1 << CHUNK_BITS) as usize;
+    (id >> CHUNK_BITS, bit / 64, 1 << (bit % 64))
+}
+
+impl<I> Default for IdSet<I> {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            chunks: HashMap::new(),
+            len: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<I> Clone for IdSet<I> {
+    fn clone(&self) -> Self {
+        Self {
+            chunks: self.chunks.clone(),
+            len: self.len,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<I: ElementId> IdSet<I> {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds the id; returns `true` when it was not present before.
+    pub fn insert(&mut self, id: I) -> bool {
+        let (chunk, word, mask) = split(id.raw());
+        let chunk = self
+            .chunks
+            .entry(chunk)
+            .or_insert_with(|| Box::new([0; CHUNK_WORDS]));
+        let new = chunk[word] & mask == 0;
+        chunk[word] |= mask;
+        self.len += new as usize;
+        new
+    }
+
+    /// Removes the id; returns `true` when it was present.
+    pub fn remove(&mut self, id: I) -> bool {
+        let (chunk, word, mask) = split(id.raw());
+        let Some(chunk) = self.chunks.get_mut(&chunk) else {
+            return false;
+        };
+        let present = chunk[word] & mask != 0;
+        chunk[word] &= !mask;
+        self.len -= present as usize;
+        present
+    }
+
+    #[inline]
+    pub fn contains(&self, id: I) -> bool {
+        let (chunk, word, mask) = split(id.raw());
+        self.chunks
+            .get(&chunk)
+            .is_some_and(|chunk| chunk[word] & mask != 0)
+    }
+
+    pub fn clear(&mut self) {
+        self.chunks.clear();
+        self.len = 0;
+    }
+
+    /// Iterates over the ids in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = I> + '_ {
+        let mut keys: Vec<i64> = self.chunks.keys().copied().collect();
+        keys.sort_unstable();
+        keys.into_iter().flat_map(move |key| {
+            let chunk = &self.chunks[&key];
+            chunk.iter().enumerate().flat_map(move |(w, &word)| {
+                (0..64)
+                    .filter(move |bit| word & (1 << bit) != 0)
+                    .map(move |bit| I::from_raw((key << CHUNK_BITS) + (w * 64 + bit) as i64))
+            })
+        })
+    }
+}
+
+impl<I: ElementId> Extend<I> for IdSet<I> {
+    fn extend<T: IntoIterator<Item = I>>(&mut self, iter: T) {
+        for id in iter {
+            self.insert(id);
+        }
+    }
+}
+
+impl<I: ElementId> FromIterator<I> for IdSet<I> {
+    fn from_iter<T: IntoIterator<Item = I>>(iter: T) -> Self {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<I: ElementId + fmt::Debug> fmt::Debug for IdSet<I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
@@ -0,0 +1,283 @@
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::io::{self, SeekFrom};
+use std::ops::Deref;
+
+use crate::blob::{Blobs, Block};
+use crate::data::{
+    primitive::{Primitive, PrimitiveType},
+    OSMDataBlob, PrimitiveBlock,
+};
+use crate::error::{Error, Result};
+
+/// Position and size of a blob in a file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BlobInfo {
+    /// Byte offset of the blob (the start of its length prefix) in the file.
+    pub offset: u64,
+    /// The `type` field of the `BlobHeader` (`OSMHeader` or `OSMData`).
+    pub blob_type: String,
+    /// Size of the encoded `BlobHeader`.
+    pub header_size: u32,
+    /// Size of the encoded `Blob`.
+    pub data_size: u32,
+    /// First and last element of the blob, see [`Blobs::index_with_id_ranges`].
+    pub id_range: Option<IdRange>,
+}
+
+impl BlobInfo {
+    /// Byte offset of the blob following this one.
+    #[inline]
+    pub fn end_offset(&self) -> u64 {
+        self.offset + 4 + self.header_size as u64 + self.data_size as u64
+    }
+
+    #[inline]
+    pub fn is_data(&self) -> bool {
+        self.blob_type == PrimitiveBlock::BLOB_TYPE
+    }
+}
+
+/// Type and id of the first and last element in a block.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IdRange {
+    pub first: (PrimitiveType, i64),
+    pub last: (PrimitiveType, i64),
+}
+
+/// Compares elements in the order of `Sort.Type_then_ID` (nodes, ways, relations).
+#[inline]
+pub(crate) fn cmp_type_then_id(a: (PrimitiveType, i64), b: (PrimitiveType, i64)) -> Ordering {
+    (a.0.bits(), a.1).cmp(&(b.0.bits(), b.1))
+}
+
+/// Binary search over blocks sorted by type and id for the block containing
+/// the element.
+///
+/// `range(i)` is the id range of the `i`-th block, `None` for blocks without
+/// elements; those are skipped by probing the next block with elements.
+pub(crate) fn search_blocks<E>(
+    len: usize,
+    (primitive_type, id): (PrimitiveType, i64),
+    mut range: impl FnMut(usize) -> Result<Option<IdRange>, E>,
+) -> Result<Option<usize>, E> {
+    let (mut low, mut high) = (0, len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        let mut probe = None;
+        for i in mid..high {
+            if let Some(r) = range(i)? {
+                probe = Some((i, r));
+                break;
+            }
+        }
+        // blocks `mid..i` are empty
+        let Some((i, r)) = probe else {
+            high = mid;
+            continue;
+        };
+        match r.cmp_element(primitive_type, id) {
+            Ordering::Less => high = mid,
+            Ordering::Greater => low = i + 1,
+            Ordering::Equal => return Ok(Some(i)),
+        }
+    }
+    Ok(None)
+}
+
+impl IdRange {
+    /// Compares the element with this range: `Less` when the element is
+    /// before the first element, `Greater` when it is after the last.
+    pub fn cmp_element(&self, primitive_type: PrimitiveType, id: i64) -> Ordering {
+        let element = (primitive_type, id);
+        if cmp_type_then_id(element, self.first).is_lt() {
+            Ordering::Less
+        } else if cmp_type_then_id(element, self.last).is_gt() {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    }
+
+    #[inline]
+    pub fn contains(&self, primitive_type: PrimitiveType, id: i64) -> bool {
+        self.cmp_element(primitive_type, id).is_eq()
+    }
+}
+
+fn type_and_id(primitive: &Primitive<'_>) -> (PrimitiveType, i64) {
+    match primitive {
+        Primitive::Node(n) => (PrimitiveType::NODE, n.id.0),
+        Primitive::Way(w) => (PrimitiveType::WAY, w.id.0),
+        Primitive::Relation(r) => (PrimitiveType::RELATION, r.id.0),
+        Primitive::ChangeSet(c) => (PrimitiveType::CHANGE_SET, c.id.0),
+    }
+}
+
+impl PrimitiveBlock {
+    /// Type and id of the first and last element in this block.
+    pub fn id_range(&self) -> Option<IdRange> {
+        let mut primitives = self.primitives();
+        let first = type_and_id(&primitives.next()?);
+        let last = primitives.last().map_or(first, |p| type_and_id(&p));
+        Some(IdRange { first, last })
+    }
+}
+
+/// Positions of all blobs in a file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BlobIndex {
+    blobs: Vec<BlobInfo>,
+}
+
+impl Deref for BlobIndex {
+    type Target = [BlobInfo];
+    #[inline]
+    fn deref(&self) -> &[BlobInfo] {
+        &self.blobs
+    }
+}
+
+impl BlobIndex {
+    /// Iterates over the data blobs together with their index.
+    pub fn data_blobs(&self) -> impl Iterator<Item = (usize, &BlobInfo)> {
+        self.blobs.iter().enumerate().filter(|(_, b)| b.is_data())
+    }
+
+    /// Finds the data blob containing the given element.
+    ///
+    /// Requires the id ranges of a file sorted by type and id
+    /// (see [`Blobs::index_with_id_ranges`]).
+    pub fn find(&self, primitive_type: PrimitiveType, id: i64) -> Option<usize> {
+        // other blobs have no id range and are skipped
+        let Ok(found) = search_blocks(self.blobs.len(), (primitive_type, id), |i| {
+            Ok::<_, Infallible>(self.blobs[i].id_range)
+        });
+        found
+    }
+}
+
+impl<'l> IntoIterator for &'l BlobIndex {
+    type Item = &'l BlobInfo;
+    type IntoIter = std::slice::Iter<'l, BlobInfo>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.blobs.iter()
+    }
+}
+
+impl<R: io::BufRead + io::Seek> Blobs<R> {
+    /// Builds an index of all blobs in one pass that skips the blob contents.
+    ///
+    /// The reader is positioned at the start of the file afterwards.
+    pub fn index(&mut self) -> Result<BlobIndex> {
+        self.rewind()?;
+        let mut blobs = Vec::new();
+        loop {
+            let offset = self.get_mut().stream_position()?;
+            let Some((header_size, header)) = self.next_blob_header()? else {
+                break;
+            };
+            let data_size = header.datasize() as u32;
+            self.get_mut().seek(SeekFrom::Current(data_size as i64))?;
+            blobs.push(BlobInfo {
+                offset,
+                blob_type: header.type_().to_string(),
+                header_size,
+                data_size,
+                id_range: None,
+            });
+        }
+        self.rewind()?;
+        Ok(BlobIndex { blobs })
+    }
+
+    /// Builds an index of all blobs and decodes every data blob to record
+    /// its first and last element.
+    ///
+    /// The reader is positioned at the start of the file afterwards.
+    pub fn index_with_id_ranges(&mut self) -> Result<BlobIndex> {
+        let mut index = self.index()?;
+        for info in &mut index.blobs {
+            if info.is_data() {
+                info.id_range = self.read_blob_at(info.offset)?.decode()?.id_range();
+            }
+        }
+        self.rewind()?;
+        Ok(index)
+    }
+
+    /// Positions the reader so the next blob is the `i`-th blob of the index.
+    pub fn seek_to_blob(&mut self, index: &BlobIndex, i: usize) -> Result<()> {
+        let info = index
+            .get(i)
+            .ok_or_else(|| Error::from(io::ErrorKind::InvalidInput))?;
+        self.get_mut().seek(SeekFrom::Start(info.offset))?;
+        Ok(())
+    }
+
+    /// Reads the data blob at the given byte offset.
+    pub fn read_blob_at(&mut self, offset: u64) -> Result<OSMDataBlob> {
+        self.get_mut().seek(SeekFrom::Start(offset))?;
+        match self.next_blob()? {
+            Some((header, blob)) if header.type_() == PrimitiveBlock::BLOB_TYPE => {
+                Ok(OSMDataBlob::new(header, blob))
+            }
+            Some((header, _)) => Err(Error::UnexpectedBlobType(header.type_().to_string())),
+            None => Err(io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{builder::PrimitiveBlockBuilder, node::NodeId, way::WayId, Meta};
+    use crate::header::HeaderBlock;
+    use crate::writer::PbfWriter;
+
+    /// Data blobs with nodes 1-3, no elements, nodes 10-12, no elements and ways 5-6.
+    fn file() -> Vec<u8> {
+        let mut writer = PbfWriter::new(Vec::new(), &HeaderBlock::new()).unwrap();
+        let empty = PrimitiveBlockBuilder::new().build();
+        for ids in [1..4, 10..13] {
+            for id in ids {
+                let tags: [(&str, &str); 0] = [];
+                writer
+                    .write_node(NodeId(id), 0, 0, tags, &Meta::default())
+                    .unwrap();
+            }
+            writer.write_block(&empty).unwrap();
+        }
+        for id in 5..7 {
+            let tags: [(&str, &str); 0] = [];
+            writer
+                .write_way(WayId(id), [NodeId(1)], tags, &Meta::default())
+                .unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn find_skips_blobs_without_elements() {
+        let index = Blobs::from_bytes(file()).index_with_id_ranges().unwrap();
+        assert_eq!(index.len(), 6);
+        assert_eq!(index.data_blobs().count(), 5);
+        for (primitive_type, id, blob) in [
+            (PrimitiveType::NODE, 1, Some(1)),
+            (PrimitiveType::NODE, 3, Some(1)),
+            (PrimitiveType::NODE, 11, Some(3)),
+            (PrimitiveType::WAY, 6, Some(5)),
+            (PrimitiveType::NODE, 5, None),
+            (PrimitiveType::NODE, 13, None),
+            (PrimitiveType::WAY, 1, None),
+            (PrimitiveType::RELATION, 1, None),
+        ] {
+            assert_eq!(
+                index.find(primitive_type, id),
+                blob,
+                "{primitive_type:?} {id}"
+            );
+        }
+    }
+}
@@ -1,6 +1,29 @@
+pub mod area;
 pub mod blob;
+pub mod change;
 pub mod data;
 pub mod error;
+pub mod extract;
+#[cfg(feature = "geo")]
+pub mod geo;
 pub mod header;
+pub mod history;
+pub mod id_set;
+pub mod index;
+pub mod location;
+#[cfg(feature = "mmap")]
+pub mod mmap;
+pub mod o5m;
+pub mod opl;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod reader;
+pub mod selection;
+pub mod text;
+pub mod writer;
+#[cfg(feature = "xml")]
+pub mod xml;
 
-pub use blob::{Blob, Blobs};
+pub use blob::{Blob, BlobWriter, Blobs};
+pub use reader::Reader;
+pub use writer::PbfWriter;
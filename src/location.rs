@@ -0,0 +1,386 @@
+use std::collections::HashMap;
+
+use crate::data::{
+    node::NodeId,
+    primitive::{Primitive, PrimitiveType},
+    way::Way,
+    PrimitiveBlock,
+};
+use crate::error::Result;
+
+/// Location of a node in nanodegrees.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    pub nano_lat: i64,
+    pub nano_lon: i64,
+}
+
+/// Marker for "no location stored" in packed form.
+const EMPTY: u64 = 0;
+
+/// Precision of stored locations in nanodegrees (`1e-7` degrees).
+const PRECISION: i64 = 100;
+
+impl Location {
+    #[inline]
+    pub const fn new(nano_lat: i64, nano_lon: i64) -> Self {
+        Self { nano_lat, nano_lon }
+    }
+
+    /// Latitude in degrees.
+    #[inline(always)]
+    pub fn lat(&self) -> f64 {
+        self.nano_lat as f64 * 1e-9
+    }
+
+    /// Longitude in degrees.
+    #[inline(always)]
+    pub fn lon(&self) -> f64 {
+        self.nano_lon as f64 * 1e-9
+    }
+
+    /// Packs the location into 64 bits with a precision of `1e-7` degrees.
+    ///
+    /// The coordinates are stored with a flipped sign-bit, so `0` never is a
+    /// valid location and zeroed memory means "empty".
+    #[inline]
+    fn pack(self) -> u64 {
+        #[inline]
+        fn scale(nano: i64) -> u32 {
+            let value = if nano >= 0 {
+                (nano + PRECISION / 2) / PRECISION
+            } else {
+                (nano - PRECISION / 2) / PRECISION
+            };
+            (value as i32 as u32) ^ 0x8000_0000
+        }
+        ((scale(self.nano_lat) as u64) << 32) | scale(self.nano_lon) as u64
+    }
+
+    #[inline]
+    fn unpack(packed: u64) -> Option<Self> {
+        if packed == EMPTY {
+            return None;
+        }
+        #[inline]
+        fn unscale(value: u32) -> i64 {
+            ((value ^ 0x8000_0000) as i32) as i64 * PRECISION
+        }
+        Some(Self {
+            nano_lat: unscale((packed >> 32) as u32),
+            nano_lon: unscale(packed as u32),
+        })
+    }
+}
+
+/// Stores node locations by id.
+///
+/// Locations are stored with a precision of `1e-7` degrees.
+pub trait LocationStore {
+    /// Stores a location; only file-backed stores can fail.
+    fn set(&mut self, id: NodeId, location: Location) -> Result<()>;
+
+    fn get(&self, id: NodeId) -> Option<Location>;
+
+    /// Stores the locations of all nodes in the block.
+    fn add_block(&mut self, block: &PrimitiveBlock) -> Result<()> {
+        for primitive in block.primitives().types(PrimitiveType::NODE) {
+            if let Primitive::Node(n) = primitive {
+                self.set(n.id, Location::new(n.nano_lat, n.nano_lon))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<S: LocationStore + ?Sized> LocationStore for &mut S {
+    #[inline]
+    fn set(&mut self, id: NodeId, location: Location) -> Result<()> {
+        (**self).set(id, location)
+    }
+
+    #[inline]
+    fn get(&self, id: NodeId) -> Option<Location> {
+        (**self).get(id)
+    }
+}
+
+/// In-memory store backed by a hash map, for small or sparse id ranges.
+#[derive(Clone, Debug, Default)]
+pub struct SparseLocationStore {
+    locations: HashMap<i64, u64>,
+}
+
+impl SparseLocationStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+}
+
+impl LocationStore for SparseLocationStore {
+    #[inline]
+    fn set(&mut self, id: NodeId, location: Location) -> Result<()> {
+        self.locations.insert(id.0, location.pack());
+        Ok(())
+    }
+
+    #[inline]
+    fn get(&self, id: NodeId) -> Option<Location> {
+        Location::unpack(*self.locations.get(&id.0)?)
+    }
+}
+
+/// In-memory store backed by an array indexed by id (8 bytes per id).
+///
+/// Suitable for dense id ranges like whole countries. Negative ids are kept
+/// in a separate hash map.
+#[derive(Clone, Debug, Default)]
+pub struct DenseLocationStore {
+    locations: Vec<u64>,
+    negative: SparseLocationStore,
+}
+
+impl DenseLocationStore {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a store with room for the ids `0..capacity`.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            locations: vec![EMPTY; capacity],
+            negative: SparseLocationStore::new(),
+        }
+    }
+}
+
+impl LocationStore for DenseLocationStore {
+    fn set(&mut self, id: NodeId, location: Location) -> Result<()> {
+        if id.0 < 0 {
+            return self.negative.set(id, location);
+        }
+        let index = id.0 as usize;
+        if index >= self.locations.len() {
+            let len = (index + 1).max(self.locations.len() * 2);
+            self.locations.resize(len, EMPTY);
+        }
+        self.locations[index] = location.pack();
+        Ok(())
+    }
+
+    #[inline]
+    fn get(&self, id: NodeId) -> Option<Location> {
+        if id.0 < 0 {
+            return self.negative.get(id);
+        }
+        Location::unpack(*self.locations.get(id.0 as usize)?)
+    }
+}
+
+#[cfg(feature = "mmap")]
+pub use self::mmap::MmapLocationStore;
+
+#[cfg(feature = "mmap")]
+mod mmap {
+    use std::fs::{File, OpenOptions};
+    use std::path::Path;
+
+    use memmap2::MmapMut;
+
+    use super::{Location, LocationStore, SparseLocationStore, EMPTY};
+    use crate::data::node::NodeId;
+    use crate::error::Result;
+
+    const INITIAL_CAPACITY: u64 = 1 << 20;
+
+    /// File-backed store, memory-mapped and indexed by id (8 bytes per id).
+    ///
+    /// For planet-scale data that does not fit into memory. The file grows
+    /// as needed and can be reopened later with [`MmapLocationStore::open`].
+    ///
+    /// Negative ids are kept in memory only: they are not written to the
+    /// file and are lost when the store is dropped.
+    pub struct MmapLocationStore {
+        file: File,
+        mmap: MmapMut,
+        negative: SparseLocationStore,
+    }
+
+    impl MmapLocationStore {
+        /// Creates (or truncates) the file at the given path.
+        pub fn create(path: impl AsRef<Path>) -> Result<Self> {
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(path)?;
+            file.set_len(INITIAL_CAPACITY * 8)?;
+            Self::from_file(file)
+        }
+
+        /// Opens a file created by [`MmapLocationStore::create`].
+        pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+            let file = OpenOptions::new().read(true).write(true).open(path)?;
+            Self::from_file(file)
+        }
+
+        fn from_file(file: File) -> Result<Self> {
+            // SAFETY: the file is owned by this store and only accessed through the map.
+            let mmap = unsafe { MmapMut::map_mut(&file)? };
+            Ok(Self {
+                file,
+                mmap,
+                negative: SparseLocationStore::new(),
+            })
+        }
+
+        #[inline]
+        fn capacity(&self) -> usize {
+            self.mmap.len() / 8
+        }
+
+        fn grow(&mut self, min_capacity: usize) -> Result<()> {
+            let capacity = min_capacity.max(self.capacity() * 2) as u64;
+            self.mmap.flush()?;
+            self.file.set_len(capacity * 8)?;
+            // SAFETY: see `from_file`
+            self.mmap = unsafe { MmapMut::map_mut(&self.file)? };
+            Ok(())
+        }
+
+        #[inline]
+        fn read(&self, index: usize) -> u64 {
+            let bytes = &self.mmap[index * 8..index * 8 + 8];
+            u64::from_le_bytes(bytes.try_into().unwrap())
+        }
+
+        /// Writes the changes to the file (negative ids excepted).
+        pub fn flush(&self) -> Result<()> {
+            self.mmap.flush()?;
+            Ok(())
+        }
+    }
+
+    impl LocationStore for MmapLocationStore {
+        /// Fails when the file could not be grown.
+        fn set(&mut self, id: NodeId, location: Location) -> Result<()> {
+            if id.0 < 0 {
+                return self.negative.set(id, location);
+            }
+            let index = id.0 as usize;
+            if index >= self.capacity() {
+                self.grow(index + 1)?;
+            }
+            self.mmap[index * 8..index * 8 + 8].copy_from_slice(&location.pack().to_le_bytes());
+            Ok(())
+        }
+
+        fn get(&self, id: NodeId) -> Option<Location> {
+            if id.0 < 0 {
+                return self.negative.get(id);
+            }
+            let index = id.0 as usize;
+            if index >= self.capacity() {
+                return None;
+            }
+            match self.read(index) {
+                EMPTY => None,
+                packed => Location::unpack(packed),
+            }
+        }
+    }
+}
+
+/// Locations of the nodes of a way.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WayGeometry {
+    /// Locations of all nodes that could be resolved, in order.
+    pub locations: Vec<Location>,
+    /// Referenced nodes without a location.
+    pub missing: Vec<NodeId>,
+}
+
+impl WayGeometry {
+    /// Returns `true` when all nodes could be resolved.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+
+    /// Returns `true` when the first and the last location are equal.
+    #[inline]
+    pub fn is_closed(&self) -> bool {
+        self.locations.len() > 2 && self.locations.first() == self.locations.last()
+    }
+}
+
+/// Resolves the node references of ways to locations.
+pub struct WayGeometryResolver<S> {
+    store: S,
+}
+
+impl<S: LocationStore> WayGeometryResolver<S> {
+    #[inline]
+    pub fn new(store: S) -> Self {
+        Self { store }
+    }
+
+    #[inline]
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    #[inline]
+    pub fn store_mut(&mut self) -> &mut S {
+        &mut self.store
+    }
+
+    #[inline]
+    pub fn into_store(self) -> S {
+        self.store
+    }
+
+    /// Resolves the locations of the way.
+    ///
+    /// Locations stored in the way itself (`LocationsOnWays`) are used
+    /// directly, without a lookup in the store.
+    pub fn resolve(&self, way: &Way<'_>) -> WayGeometry {
+        if way.has_locations() {
+            return WayGeometry {
+                locations: way
+                    .locations()
+                    .map(|(lat, lon)| Location::new(lat, lon))
+                    .collect(),
+                missing: Vec::new(),
+            };
+        }
+        self.resolve_refs(way.refs())
+    }
+
+    /// Resolves the locations of the given nodes.
+    pub fn resolve_refs(&self, refs: impl IntoIterator<Item = NodeId>) -> WayGeometry {
+        let mut geometry = WayGeometry::default();
+        for id in refs {
+            match self.store.get(id) {
+                Some(location) => geometry.locations.push(location),
+                None => geometry.missing.push(id),
+            }
+        }
+        geometry
+    }
+}
@@ -0,0 +1,126 @@
+use std::fs::File;
+use std::iter::FusedIterator;
+use std::path::Path;
+
+use byteorder::{BigEndian, ByteOrder};
+use bytes::Bytes;
+use memmap2::Mmap;
+use osm_pbf_proto::protobuf::Message;
+
+use crate::blob::{Block, PbfBlob, PbfBlobHeader, MAX_HEADER_SIZE, MAX_UNCOMPRESSED_DATA_SIZE};
+use crate::data::{OSMDataBlob, PrimitiveBlock};
+use crate::error::{Error, Result};
+use crate::header::{HeaderBlock, OSMHeaderBlob};
+
+/// Reads blobs from a memory-mapped file (or any other shared buffer).
+///
+/// Unlike [`Blobs`](crate::Blobs), the returned blobs are not copied out of
+/// the buffer: their data references the mapped file directly. Uncompressed
+/// blobs are also parsed without an intermediate copy.
+#[derive(Clone, Debug)]
+pub struct MmapBlobs {
+    data: Bytes,
+    pos: usize,
+}
+
+impl MmapBlobs {
+    /// Opens and maps the file at the given path.
+    ///
+    /// See [`MmapBlobs::map`] for the safety considerations.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: see `map`; the file is opened read-only.
+        unsafe { Self::map(&file) }
+    }
+
+    /// Maps the given file.
+    ///
+    /// # Safety
+    ///
+    /// The file must not be modified or truncated while the map (or any blob
+    /// or block created from it) is alive. See [`memmap2::Mmap::map`].
+    pub unsafe fn map(file: &File) -> Result<Self> {
+        let mmap = Mmap::map(file)?;
+        Ok(Self::from_bytes(Bytes::from_owner(mmap)))
+    }
+
+    #[inline]
+    pub fn from_bytes(data: Bytes) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> Bytes {
+        self.data
+    }
+
+    #[inline]
+    pub fn rewind(&mut self) {
+        self.pos = 0;
+    }
+
+    pub fn header(&mut self) -> Result<OSMHeaderBlob> {
+        match self.next_blob()? {
+            Some((header, blob)) if header.type_() == HeaderBlock::BLOB_TYPE => {
+                Ok(OSMHeaderBlob::new(header, blob))
+            }
+            Some((header, _)) => Err(Error::UnexpectedBlobType(header.type_().to_string())),
+            None => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<Bytes> {
+        let end = self.pos + len;
+        if end > self.data.len() {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        let bytes = self.data.slice(self.pos..end);
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn next_blob(&mut self) -> Result<Option<(PbfBlobHeader, PbfBlob)>> {
+        if self.pos >= self.data.len() {
+            return Ok(None); // Expected EOF
+        }
+        let header_size = BigEndian::read_u32(&self.take(4)?);
+        if header_size > MAX_HEADER_SIZE {
+            return Err(Error::BlobHeaderToLarge);
+        }
+
+        let header = PbfBlobHeader::parse_from_tokio_bytes(&self.take(header_size as usize)?)?;
+        let data_size = header.datasize() as usize;
+        if data_size > MAX_UNCOMPRESSED_DATA_SIZE {
+            return Err(Error::BlobDataToLarge);
+        }
+
+        let blob = PbfBlob::parse_from_tokio_bytes(&self.take(data_size)?)?;
+        Ok(Some((header, blob)))
+    }
+}
+
+impl Iterator for MmapBlobs {
+    type Item = Result<OSMDataBlob>;
+
+    fn next(&mut self) -> Option<Result<OSMDataBlob>> {
+        loop {
+            match self.next_blob() {
+                Err(e) => {
+                    // don't return the same error again
+                    self.pos = self.data.len();
+                    return Some(Err(e));
+                }
+                Ok(None) => {
+                    return None;
+                }
+                Ok(Some((header, blob))) if header.type_() == PrimitiveBlock::BLOB_TYPE => {
+                    return Some(Ok(OSMDataBlob::new(header, blob)));
+                }
+                // skip unsupported blobs and header-blobs
+                _ => {}
+            }
+        }
+    }
+}
+
+impl FusedIterator for MmapBlobs {}
@@ -0,0 +1,738 @@
+//! Reading `.o5m` (and `.o5c`) files.
+//!
+//! o5m stores the elements as length-prefixed datasets with delta coded ids,
+//! coordinates and metadata, and references to recently used strings. See
+//! <https://wiki.openstreetmap.org/wiki/O5m>.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::iter::FusedIterator;
+use std::path::Path;
+
+use crate::data::{
+    changeset::ChangeSetId,
+    node::{NodeId, OwnedNode},
+    primitive::OwnedPrimitive,
+    relation::{OwnedMember, OwnedRelation, RelationId},
+    way::{OwnedWay, WayId},
+    OwnedMeta,
+};
+use crate::error::{Error, Result};
+use crate::header::{BoundingBox, HeaderBlock};
+
+const NODE: u8 = 0x10;
+const WAY: u8 = 0x11;
+const RELATION: u8 = 0x12;
+const BOUNDING_BOX: u8 = 0xdb;
+const FILE_TIMESTAMP: u8 = 0xdc;
+const HEADER: u8 = 0xe0;
+const END_OF_FILE: u8 = 0xfe;
+const RESET: u8 = 0xff;
+
+/// Number of entries of the string table.
+const STRING_TABLE_SIZE: usize = 15000;
+/// Strings (or pairs) longer than this are not stored in the string table.
+const MAX_STRING_TABLE_LEN: usize = 250;
+
+/// Datasets longer than this are rejected instead of read into memory.
+const MAX_DATASET_LEN: u64 = 32 * 1024 * 1024;
+
+/// o5m stores coordinates in 100 nanodegrees.
+const GRANULARITY: i64 = 100;
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidO5m(message.into())
+}
+
+/// A cursor over the bytes of a dataset.
+struct Cursor<'a>(&'a [u8]);
+
+impl<'a> Cursor<'a> {
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn unsigned(&mut self) -> Result<u64> {
+        let mut value = 0u64;
+        for (i, &byte) in self.0.iter().enumerate().take(10) {
+            value |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                self.0 = &self.0[i + 1..];
+                return Ok(value);
+            }
+        }
+        Err(invalid("invalid or truncated number"))
+    }
+
+    #[inline]
+    fn signed(&mut self) -> Result<i64> {
+        let value = self.unsigned()?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn take(&mut self, len: usize) -> Result<Cursor<'a>> {
+        if len > self.0.len() {
+            return Err(invalid("section exceeds the dataset"));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(Cursor(head))
+    }
+}
+
+/// Current values of the delta coded fields and the string table; cleared
+/// by reset datasets.
+///
+/// The deltas are added wrapping, so corrupt files can't cause overflows.
+#[derive(Default)]
+struct State {
+    id: i64,
+    timestamp: i64,
+    changeset: i64,
+    lon: i64,
+    lat: i64,
+    way_ref: i64,
+    /// per member type (node, way, relation)
+    member_refs: [i64; 3],
+    strings: VecDeque<Vec<u8>>,
+}
+
+impl State {
+    /// Reads a string (`pair == false`) or a pair of strings, either inline
+    /// or as a reference into the string table. Returns the bytes of the
+    /// strings, each terminated by `0`.
+    fn strings(&mut self, cursor: &mut Cursor<'_>, pair: bool) -> Result<Vec<u8>> {
+        let reference = cursor.unsigned()? as usize;
+        if reference != 0 {
+            return self
+                .strings
+                .get(reference - 1)
+                .cloned()
+                .ok_or_else(|| invalid(format!("invalid string reference {reference}")));
+        }
+        let count = if pair { 2 } else { 1 };
+        let mut len = 0;
+        for _ in 0..count {
+            len += cursor.0[len..]
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or_else(|| invalid("unterminated string"))?
+                + 1;
+        }
+        let bytes = cursor.take(len)?.0.to_vec();
+        if len - count <= MAX_STRING_TABLE_LEN {
+            self.strings.push_front(bytes.clone());
+            self.strings.truncate(STRING_TABLE_SIZE);
+        }
+        Ok(bytes)
+    }
+
+    fn pair(&mut self, cursor: &mut Cursor<'_>) -> Result<(Vec<u8>, Vec<u8>)> {
+        let mut bytes = self.strings(cursor, true)?;
+        let split = bytes.iter().position(|&b| b == 0).unwrap_or_default();
+        let mut second = bytes.split_off(split + 1);
+        bytes.pop();
+        second.pop();
+        Ok((bytes, second))
+    }
+
+    fn string_pair(&mut self, cursor: &mut Cursor<'_>) -> Result<(String, String)> {
+        let (first, second) = self.pair(cursor)?;
+        Ok((String::from_utf8(first)?, String::from_utf8(second)?))
+    }
+
+    fn meta(&mut self, cursor: &mut Cursor<'_>) -> Result<OwnedMeta> {
+        let mut meta = OwnedMeta {
+            version: cursor.unsigned()? as u32,
+            ..OwnedMeta::default()
+        };
+        if meta.version == 0 {
+            return Ok(meta);
+        }
+        self.timestamp = self.timestamp.wrapping_add(cursor.signed()?);
+        if self.timestamp == 0 {
+            return Ok(meta);
+        }
+        meta.timestamp = self.timestamp.wrapping_mul(1000);
+        self.changeset = self.changeset.wrapping_add(cursor.signed()?);
+        meta.changeset = ChangeSetId(self.changeset);
+        let (uid, user) = self.pair(cursor)?;
+        meta.uid = if uid.is_empty() {
+            0
+        } else {
+            Cursor(&uid).unsigned()? as i32
+        };
+        meta.user = String::from_utf8(user)?;
+        Ok(meta)
+    }
+
+    fn tags(&mut self, cursor: &mut Cursor<'_>) -> Result<Vec<(String, String)>> {
+        let mut tags = Vec::new();
+        while !cursor.is_empty() {
+            tags.push(self.string_pair(cursor)?);
+        }
+        Ok(tags)
+    }
+
+    fn node(&mut self, mut cursor: Cursor<'_>) -> Result<OwnedNode> {
+        self.id = self.id.wrapping_add(cursor.signed()?);
+        let mut meta = self.meta(&mut cursor)?;
+        let (mut nano_lat, mut nano_lon) = (0, 0);
+        // deleted nodes (in .o5c files) have no location
+        if cursor.is_empty() {
+            meta.visible = false;
+        } else {
+            self.lon = self.lon.wrapping_add(cursor.signed()?);
+            self.lat = self.lat.wrapping_add(cursor.signed()?);
+            nano_lat = self.lat.wrapping_mul(GRANULARITY);
+            nano_lon = self.lon.wrapping_mul(GRANULARITY);
+        }
+        Ok(OwnedNode {
+            id: NodeId(self.id),
+            nano_lat,
+            nano_lon,
+            tags: self.tags(&mut cursor)?,
+            meta,
+        })
+    }
+
+    fn way(&mut self, mut cursor: Cursor<'_>) -> Result<OwnedWay> {
+        self.id = self.id.wrapping_add(cursor.signed()?);
+        let mut meta = self.meta(&mut cursor)?;
+        let mut refs = Vec::new();
+        if cursor.is_empty() {
+            meta.visible = false;
+        } else {
+            let len = cursor.unsigned()? as usize;
+            let mut section = cursor.take(len)?;
+            while !section.is_empty() {
+                self.way_ref = self.way_ref.wrapping_add(section.signed()?);
+                refs.push(NodeId(self.way_ref));
+            }
+        }
+        Ok(OwnedWay {
+            id: WayId(self.id),
+            refs,
+            locations: Vec::new(),
+            tags: self.tags(&mut cursor)?,
+            meta,
+        })
+    }
+
+    fn relation(&mut self, mut cursor: Cursor<'_>) -> Result<OwnedRelation> {
+        self.id = self.id.wrapping_add(cursor.signed()?);
+        let mut meta = self.meta(&mut cursor)?;
+        let mut members = Vec::new();
+        if cursor.is_empty() {
+            meta.visible = false;
+        } else {
+            let len = cursor.unsigned()? as usize;
+            let mut section = cursor.take(len)?;
+            while !section.is_empty() {
+                let delta = section.signed()?;
+                // the role, prefixed with the member type
+                let mut role = self.strings(&mut section, false)?;
+                role.pop();
+                let member_type = match role.first() {
+                    Some(t @ b'0'..=b'2') => (t - b'0') as usize,
+                    _ => return Err(invalid("invalid member type")),
+                };
+                let role = String::from_utf8(role[1..].to_vec())?;
+                let id = self.member_refs[member_type].wrapping_add(delta);
+                self.member_refs[member_type] = id;
+                members.push(match member_type {
+                    0 => OwnedMember::Node(NodeId(id), role),
+                    1 => OwnedMember::Way(WayId(id), role),
+                    _ => OwnedMember::Relation(RelationId(id), role),
+                });
+            }
+        }
+        Ok(OwnedRelation {
+            id: RelationId(self.id),
+            members,
+            tags: self.tags(&mut cursor)?,
+            meta,
+        })
+    }
+}
+
+/// Reads the elements of an o5m file as [`OwnedPrimitive`]s.
+///
+/// The bounding box and the file timestamp (as replication timestamp) are
+/// available through [`O5mReader::header`]. Deleted elements of `.o5c`
+/// change files are returned with `visible == false`.
+pub struct O5mReader<R> {
+    read: R,
+    buf: Vec<u8>,
+    state: State,
+    header: HeaderBlock,
+    pending: Option<OwnedPrimitive>,
+    done: bool,
+}
+
+impl O5mReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> O5mReader<R> {
+    /// Creates the reader and reads everything up to the first element.
+    pub fn new(mut read: R) -> Result<Self> {
+        let mut first = [0u8];
+        read.read_exact(&mut first)?;
+        if first[0] != RESET {
+            return Err(invalid("missing reset at the start of the file"));
+        }
+        let mut header = HeaderBlock::new();
+        header.clear_writingprogram();
+        let mut this = Self {
+            read,
+            buf: Vec::new(),
+            state: State::default(),
+            header,
+            pending: None,
+            done: false,
+        };
+        this.pending = this.read_next()?;
+        this.done = this.pending.is_none();
+        Ok(this)
+    }
+
+    /// Header with the bounding box and the file timestamp of the file.
+    #[inline]
+    pub fn header(&self) -> &HeaderBlock {
+        &self.header
+    }
+
+    /// Reads the next byte, `None` at the end of the file.
+    fn read_byte(&mut self) -> Result<Option<u8>> {
+        let mut byte = [0u8];
+        match self.read.read_exact(&mut byte) {
+            Ok(()) => Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Reads the length and content of a dataset into `buf`.
+    fn read_dataset(&mut self) -> Result<()> {
+        let mut len = 0u64;
+        for i in 0..10 {
+            let byte = self.read_byte()?.ok_or(io::ErrorKind::UnexpectedEof)?;
+            len |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                if len > MAX_DATASET_LEN {
+                    return Err(invalid(format!("dataset of {len} bytes is too large")));
+                }
+                // read without allocating the full length up-front, the file
+                // may be truncated
+                self.buf.clear();
+                (&mut self.read).take(len).read_to_end(&mut self.buf)?;
+                if self.buf.len() as u64 != len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                return Ok(());
+            }
+        }
+        Err(invalid("invalid dataset length"))
+    }
+
+    fn read_next(&mut self) -> Result<Option<OwnedPrimitive>> {
+        loop {
+            let dataset = match self.read_byte()? {
+                None | Some(END_OF_FILE) => return Ok(None),
+                Some(RESET) => {
+                    self.state = State::default();
+                    continue;
+                }
+                // other single-byte datasets
+                Some(0xf0..=0xfd) => continue,
+                Some(dataset) => dataset,
+            };
+            self.read_dataset()?;
+            let buf = std::mem::take(&mut self.buf);
+            let result = self.parse_dataset(dataset, Cursor(&buf));
+            self.buf = buf;
+            if let Some(primitive) = result? {
+                return Ok(Some(primitive));
+            }
+        }
+    }
+
+    fn parse_dataset(
+        &mut self,
+        dataset: u8,
+        mut cursor: Cursor<'_>,
+    ) -> Result<Option<OwnedPrimitive>> {
+        match dataset {
+            NODE => return Ok(Some(OwnedPrimitive::Node(self.state.node(cursor)?))),
+            WAY => return Ok(Some(OwnedPrimitive::Way(self.state.way(cursor)?))),
+            RELATION => return Ok(Some(OwnedPrimitive::Relation(self.state.relation(cursor)?))),
+            BOUNDING_BOX => {
+                let left = cursor.signed()?.wrapping_mul(GRANULARITY);
+                let bottom = cursor.signed()?.wrapping_mul(GRANULARITY);
+                let right = cursor.signed()?.wrapping_mul(GRANULARITY);
+                let top = cursor.signed()?.wrapping_mul(GRANULARITY);
+                self.header.set_bbox(Some(BoundingBox {
+                    left,
+                    right,
+                    top,
+                    bottom,
+                }));
+            }
+            FILE_TIMESTAMP => {
+                let timestamp = cursor.signed()?;
+                self.header.set_replication_timestamp(timestamp);
+            }
+            HEADER if cursor.0 != b"o5m2" && cursor.0 != b"o5c2" => {
+                return Err(invalid(format!(
+                    "unsupported format `{}`",
+                    String::from_utf8_lossy(cursor.0)
+                )));
+            }
+            // e.g. the header, jump and sync datasets
+            _ => {}
+        }
+        Ok(None)
+    }
+}
+
+impl<R: BufRead> Iterator for O5mReader<R> {
+    type Item = Result<OwnedPrimitive>;
+
+    fn next(&mut self) -> Option<Result<OwnedPrimitive>> {
+        if let Some(primitive) = self.pending.take() {
+            return Some(Ok(primitive));
+        }
+        if self.done {
+            return None;
+        }
+        match self.read_next() {
+            Ok(Some(primitive)) => Some(Ok(primitive)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // don't return the same error again
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> FusedIterator for O5mReader<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unsigned(mut value: u64) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        while value >= 0x80 {
+            bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        bytes.push(value as u8);
+        bytes
+    }
+
+    fn signed(value: i64) -> Vec<u8> {
+        unsigned(((value << 1) ^ (value >> 63)) as u64)
+    }
+
+    /// An inline string (or pair) with the terminating `0` bytes.
+    fn inline(strings: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = vec![0];
+        for s in strings {
+            bytes.extend_from_slice(s);
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn dataset(kind: u8, parts: &[&[u8]]) -> Vec<u8> {
+        let body = parts.concat();
+        [&[kind][..], &unsigned(body.len() as u64), &body].concat()
+    }
+
+    fn file(format: &[u8], datasets: &[Vec<u8>]) -> Vec<u8> {
+        [
+            &[RESET][..],
+            &dataset(HEADER, &[format]),
+            &datasets.concat(),
+        ]
+        .concat()
+    }
+
+    fn read(bytes: &[u8]) -> Result<Vec<OwnedPrimitive>> {
+        O5mReader::new(bytes)?.collect()
+    }
+
+    fn node(primitive: &OwnedPrimitive) -> &OwnedNode {
+        match primitive {
+            OwnedPrimitive::Node(node) => node,
+            p => panic!("expected a node, got {p:?}"),
+        }
+    }
+
+    #[test]
+    fn string_references() {
+        let bytes = file(
+            b"o5m2",
+            &[
+                // version 1, timestamp, changeset and uid 5 with user "foo"
+                dataset(
+                    NODE,
+                    &[
+                        &signed(1),
+                        &unsigned(1),
+                        &signed(1_356_373_800),
+                        &signed(10),
+                        &inline(&[&[5], b"foo"]),
+                        &signed(87_000_000),
+                        &signed(494_000_000),
+                        &inline(&[b"amenity", b"bench"]),
+                    ],
+                ),
+                // the user and the tag as references
+                dataset(
+                    NODE,
+                    &[
+                        &signed(2),
+                        &unsigned(2),
+                        &signed(60),
+                        &signed(1),
+                        &unsigned(2),
+                        &signed(-1),
+                        &signed(1),
+                        &inline(&[b"name", b"x"]),
+                        &unsigned(2),
+                    ],
+                ),
+            ],
+        );
+        let primitives = read(&bytes).unwrap();
+        let (a, b) = (node(&primitives[0]), node(&primitives[1]));
+        assert_eq!((a.id, b.id), (NodeId(1), NodeId(3)));
+        assert_eq!((a.nano_lon, a.nano_lat), (8_700_000_000, 49_400_000_000));
+        assert_eq!((b.nano_lon, b.nano_lat), (8_699_999_900, 49_400_000_100));
+        assert_eq!((a.meta.uid, a.meta.user.as_str()), (5, "foo"));
+        assert_eq!((b.meta.uid, b.meta.user.as_str()), (5, "foo"));
+        assert_eq!(
+            (b.meta.timestamp, b.meta.changeset),
+            (1_356_373_860_000, ChangeSetId(11))
+        );
+        let tag = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(a.tags, [tag("amenity", "bench")]);
+        assert_eq!(b.tags, [tag("name", "x"), tag("amenity", "bench")]);
+    }
+
+    #[test]
+    fn reset_clears_deltas_and_strings() {
+        let first = dataset(
+            NODE,
+            &[
+                &signed(10),
+                &unsigned(0),
+                &signed(5),
+                &signed(5),
+                &inline(&[b"a", b"b"]),
+            ],
+        );
+        let after_reset = dataset(
+            NODE,
+            &[
+                &signed(3),
+                &unsigned(0),
+                &signed(1),
+                &signed(2),
+                &inline(&[b"c", b"d"]),
+                &unsigned(1),
+            ],
+        );
+        let bytes = file(b"o5m2", &[first.clone(), vec![RESET], after_reset]);
+        let primitives = read(&bytes).unwrap();
+        let node = node(&primitives[1]);
+        assert_eq!(node.id, NodeId(3));
+        assert_eq!((node.nano_lon, node.nano_lat), (100, 200));
+        let tag = |k: &str, v: &str| (k.to_string(), v.to_string());
+        assert_eq!(node.tags, [tag("c", "d"), tag("c", "d")]);
+
+        // the string of the first node is gone after the reset
+        let reference = dataset(
+            NODE,
+            &[
+                &signed(1),
+                &unsigned(0),
+                &signed(0),
+                &signed(0),
+                &unsigned(1),
+            ],
+        );
+        let bytes = file(b"o5m2", &[first, vec![RESET], reference]);
+        assert!(matches!(read(&bytes), Err(Error::InvalidO5m(_))));
+    }
+
+    #[test]
+    fn unsupported_header() {
+        for format in [&b"o5m3"[..], b"xml", b""] {
+            let bytes = file(format, &[]);
+            assert!(matches!(
+                O5mReader::new(&bytes[..]),
+                Err(Error::InvalidO5m(_))
+            ));
+        }
+        assert!(read(&file(b"o5c2", &[])).unwrap().is_empty());
+        assert!(matches!(
+            O5mReader::new(&[NODE][..]),
+            Err(Error::InvalidO5m(_))
+        ));
+    }
+
+    #[test]
+    fn deleted_node_without_location() {
+        let bytes = file(
+            b"o5c2",
+            &[dataset(
+                NODE,
+                &[
+                    &signed(7),
+                    &unsigned(3),
+                    &signed(100),
+                    &signed(5),
+                    &inline(&[&[5], b"foo"]),
+                ],
+            )],
+        );
+        let primitives = read(&bytes).unwrap();
+        let node = node(&primitives[0]);
+        assert_eq!(node.id, NodeId(7));
+        assert!(!node.meta.visible);
+        assert_eq!(node.meta.version, 3);
+        assert_eq!((node.nano_lat, node.nano_lon), (0, 0));
+        assert!(node.tags.is_empty());
+    }
+
+    #[test]
+    fn relation_members() {
+        let members = [
+            &signed(5)[..],
+            &inline(&[b"0stop"]),
+            &signed(7),
+            &inline(&[b"1"]),
+            &signed(9),
+            &inline(&[b"2sub"]),
+            // deltas are per member type, the role is a reference
+            &signed(1),
+            &unsigned(3),
+            &signed(-2),
+            &unsigned(2),
+        ]
+        .concat();
+        let bytes = file(
+            b"o5m2",
+            &[dataset(
+                RELATION,
+                &[
+                    &signed(4),
+                    &unsigned(0),
+                    &unsigned(members.len() as u64),
+                    &members,
+                    &inline(&[b"type", b"route"]),
+                ],
+            )],
+        );
+        let primitives = read(&bytes).unwrap();
+        let OwnedPrimitive::Relation(relation) = &primitives[0] else {
+            panic!("expected a relation");
+        };
+        assert_eq!(relation.id, RelationId(4));
+        assert_eq!(
+            relation.members,
+            [
+                OwnedMember::Node(NodeId(5), "stop".to_string()),
+                OwnedMember::Way(WayId(7), String::new()),
+                OwnedMember::Relation(RelationId(9), "sub".to_string()),
+                OwnedMember::Node(NodeId(6), "stop".to_string()),
+                OwnedMember::Way(WayId(5), String::new()),
+            ]
+        );
+        assert_eq!(relation.tags, [("type".to_string(), "route".to_string())]);
+        assert!(relation.meta.visible);
+
+        let invalid = [&signed(1)[..], &inline(&[b"3x"])].concat();
+        let bytes = file(
+            b"o5m2",
+            &[dataset(
+                RELATION,
+                &[
+                    &signed(1),
+                    &unsigned(0),
+                    &unsigned(invalid.len() as u64),
+                    &invalid,
+                ],
+            )],
+        );
+        assert!(matches!(read(&bytes), Err(Error::InvalidO5m(_))));
+    }
+
+    #[test]
+    fn way_refs() {
+        let refs = [signed(3), signed(-1), signed(10)].concat();
+        let bytes = file(
+            b"o5m2",
+            &[dataset(
+                WAY,
+                &[
+                    &signed(2),
+                    &unsigned(0),
+                    &unsigned(refs.len() as u64),
+                    &refs,
+                ],
+            )],
+        );
+        let primitives = read(&bytes).unwrap();
+        let OwnedPrimitive::Way(way) = &primitives[0] else {
+            panic!("expected a way");
+        };
+        assert_eq!(way.refs, [NodeId(3), NodeId(2), NodeId(12)]);
+    }
+
+    #[test]
+    fn malformed_input() {
+        // a dataset length of `i64::MAX`
+        let bytes = [
+            0xff, 0x10, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f,
+        ];
+        assert!(matches!(
+            O5mReader::new(&bytes[..]),
+            Err(Error::InvalidO5m(_))
+        ));
+
+        // truncated dataset
+        let mut bytes = file(
+            b"o5m2",
+            &[dataset(
+                NODE,
+                &[&signed(1), &unsigned(0), &signed(0), &signed(0)],
+            )],
+        );
+        bytes.pop();
+        assert!(matches!(read(&bytes), Err(Error::IoError(_))));
+
+        // overflowing deltas
+        let node = |delta| {
+            dataset(
+                NODE,
+                &[&signed(delta), &unsigned(0), &signed(delta), &signed(delta)],
+            )
+        };
+        let bytes = file(b"o5m2", &[node(i64::MAX), node(i64::MAX)]);
+        assert_eq!(read(&bytes).unwrap().len(), 2);
+    }
+}
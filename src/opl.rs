@@ -0,0 +1,649 @@
+//! Reading and writing OPL ("Object Per Line") files, the text format of
+//! [osmium](https://osmcode.org/opl-file-format/).
+//!
+//! ```text
+//! n1 v1 dV c1 t2012-12-24T18:30:00Z i1 ufoo Tamenity=bench x8.7 y49.4
+//! w2 v1 dV c1 t2012-12-24T18:30:00Z i1 ufoo Thighway=primary Nn1,n3
+//! r3 v1 dV c1 t2012-12-24T18:30:00Z i1 ufoo Ttype=route Mw2@,n1@stop
+//! ```
+
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::iter::FusedIterator;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::data::{
+    changeset::{ChangeSet, ChangeSetId},
+    node::{NodeId, OwnedNode},
+    primitive::{OwnedPrimitive, Primitive},
+    relation::{Member, OwnedMember, OwnedRelation, RelationId},
+    way::{OwnedWay, WayId},
+    Meta, OwnedMeta,
+};
+use crate::error::{Error, Result};
+use crate::text::{format_nano_degrees, format_timestamp, parse_nano_degrees, parse_timestamp};
+
+/// Writes elements as OPL, one line per element.
+///
+/// All metadata fields are written, like osmium does.
+pub struct OplWriter<W: Write> {
+    write: W,
+    line: String,
+}
+
+impl<W: Write> OplWriter<W> {
+    #[inline]
+    pub fn new(write: W) -> Self {
+        Self {
+            write,
+            line: String::new(),
+        }
+    }
+
+    fn start(&mut self, t: char, id: i64, meta: &Meta<'_>) {
+        self.line.clear();
+        let _ = write!(
+            self.line,
+            "{t}{id} v{} d{} c{} t{} i{} u",
+            meta.version,
+            if meta.visible { 'V' } else { 'D' },
+            meta.changeset.0,
+            if meta.timestamp != 0 {
+                format_timestamp(meta.timestamp)
+            } else {
+                String::new()
+            },
+            meta.uid,
+        );
+        escape(&mut self.line, meta.user);
+    }
+
+    fn tags<K, V>(&mut self, tags: impl IntoIterator<Item = (K, V)>)
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.line.push_str(" T");
+        for (i, (key, value)) in tags.into_iter().enumerate() {
+            if i > 0 {
+                self.line.push(',');
+            }
+            escape(&mut self.line, key.as_ref());
+            self.line.push('=');
+            escape(&mut self.line, value.as_ref());
+        }
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.line.push('\n');
+        self.write.write_all(self.line.as_bytes())?;
+        Ok(())
+    }
+
+    /// Writes a node with the given coordinates in nanodegrees.
+    pub fn write_node<K, V>(
+        &mut self,
+        id: NodeId,
+        nano_lat: i64,
+        nano_lon: i64,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.start('n', id.0, meta);
+        self.tags(tags);
+        // deleted nodes have no location
+        if meta.visible {
+            let _ = write!(
+                self.line,
+                " x{} y{}",
+                format_nano_degrees(nano_lon),
+                format_nano_degrees(nano_lat)
+            );
+        } else {
+            self.line.push_str(" x y");
+        }
+        self.end()
+    }
+
+    fn write_way_nodes<K, V>(
+        &mut self,
+        id: WayId,
+        nodes: impl IntoIterator<Item = (NodeId, Option<(i64, i64)>)>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.start('w', id.0, meta);
+        self.tags(tags);
+        self.line.push_str(" N");
+        for (i, (node, location)) in nodes.into_iter().enumerate() {
+            if i > 0 {
+                self.line.push(',');
+            }
+            let _ = write!(self.line, "n{}", node.0);
+            if let Some((nano_lat, nano_lon)) = location {
+                let _ = write!(
+                    self.line,
+                    "x{}y{}",
+                    format_nano_degrees(nano_lon),
+                    format_nano_degrees(nano_lat)
+                );
+            }
+        }
+        self.end()
+    }
+
+    /// Writes a way referencing the given nodes.
+    pub fn write_way<K, V>(
+        &mut self,
+        id: WayId,
+        refs: impl IntoIterator<Item = NodeId>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.write_way_nodes(id, refs.into_iter().map(|id| (id, None)), tags, meta)
+    }
+
+    /// Writes a way with the locations `(nano_lat, nano_lon)` of its nodes
+    /// (like `n1x8.7y49.4`).
+    pub fn write_way_with_locations<K, V>(
+        &mut self,
+        id: WayId,
+        nodes: impl IntoIterator<Item = (NodeId, (i64, i64))>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let nodes = nodes.into_iter().map(|(id, l)| (id, Some(l)));
+        self.write_way_nodes(id, nodes, tags, meta)
+    }
+
+    /// Writes a relation with the given members.
+    pub fn write_relation<'m, K, V>(
+        &mut self,
+        id: RelationId,
+        members: impl IntoIterator<Item = Member<'m>>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.start('r', id.0, meta);
+        self.tags(tags);
+        self.line.push_str(" M");
+        for (i, member) in members.into_iter().enumerate() {
+            if i > 0 {
+                self.line.push(',');
+            }
+            let (t, id) = match member {
+                Member::Node(id, _) => ('n', id.0),
+                Member::Way(id, _) => ('w', id.0),
+                Member::Relation(id, _) => ('r', id.0),
+            };
+            let _ = write!(self.line, "{t}{id}@");
+            escape(&mut self.line, member.role());
+        }
+        self.end()
+    }
+
+    pub fn write_changeset(&mut self, id: ChangeSetId) -> Result<()> {
+        writeln!(self.write, "c{}", id.0)?;
+        Ok(())
+    }
+
+    /// Writes the given primitive.
+    pub fn write_primitive(&mut self, primitive: &Primitive<'_>) -> Result<()> {
+        match primitive {
+            Primitive::Node(n) => self.write_node(n.id, n.nano_lat, n.nano_lon, n.tags(), n),
+            Primitive::Way(w) if w.has_locations() => {
+                self.write_way_with_locations(w.id, w.refs_with_locations(), w.tags(), w)
+            }
+            Primitive::Way(w) => self.write_way(w.id, w.refs(), w.tags(), w),
+            Primitive::Relation(r) => self.write_relation(r.id, r.members(), r.tags(), r),
+            Primitive::ChangeSet(c) => self.write_changeset(c.id),
+        }
+    }
+
+    /// Writes the given owned primitive.
+    pub fn write_owned(&mut self, primitive: &OwnedPrimitive) -> Result<()> {
+        match primitive {
+            OwnedPrimitive::Node(n) => self.write_node(
+                n.id,
+                n.nano_lat,
+                n.nano_lon,
+                n.tags.iter().map(|(k, v)| (k, v)),
+                &n.meta.as_meta(),
+            ),
+            OwnedPrimitive::Way(w) if !w.locations.is_empty() => self.write_way_with_locations(
+                w.id,
+                w.refs.iter().copied().zip(w.locations.iter().copied()),
+                w.tags.iter().map(|(k, v)| (k, v)),
+                &w.meta.as_meta(),
+            ),
+            OwnedPrimitive::Way(w) => self.write_way(
+                w.id,
+                w.refs.iter().copied(),
+                w.tags.iter().map(|(k, v)| (k, v)),
+                &w.meta.as_meta(),
+            ),
+            OwnedPrimitive::Relation(r) => self.write_relation(
+                r.id,
+                r.members.iter().map(OwnedMember::as_member),
+                r.tags.iter().map(|(k, v)| (k, v)),
+                &r.meta.as_meta(),
+            ),
+            OwnedPrimitive::ChangeSet(c) => self.write_changeset(c.id),
+        }
+    }
+
+    /// Flushes and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write.flush()?;
+        Ok(self.write)
+    }
+}
+
+/// Escapes like osmium: all characters except a set of printable ones are
+/// written as `%<hex code point>%`.
+fn escape(out: &mut String, s: &str) {
+    for c in s.chars() {
+        match c as u32 {
+            0x21..=0x24
+            | 0x26..=0x2b
+            | 0x2d..=0x3c
+            | 0x3e..=0x3f
+            | 0x41..=0x7e
+            | 0xa1..=0xac
+            | 0xae..=0x05ff => out.push(c),
+            code @ 0..=0xff => {
+                let _ = write!(out, "%{code:02x}%");
+            }
+            code => {
+                let _ = write!(out, "%{code:04x}%");
+            }
+        }
+    }
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::InvalidOpl(message.into())
+}
+
+/// Reverses [`escape`]; other characters are taken literally.
+fn unescape(s: &str) -> Result<String> {
+    if !s.contains('%') {
+        return Ok(s.to_string());
+    }
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('%') {
+        out.push_str(&rest[..start]);
+        let (hex, tail) = rest[start + 1..]
+            .split_once('%')
+            .ok_or_else(|| invalid(format!("unterminated escape in `{s}`")))?;
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| invalid(format!("invalid escape `%{hex}%`")))?;
+        out.push(c);
+        rest = tail;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn parse_value<T: FromStr>(field: char, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| invalid(format!("invalid value `{value}` for `{field}`")))
+}
+
+fn parse_coordinate(field: char, value: &str) -> Result<i64> {
+    parse_nano_degrees(value)
+        .ok_or_else(|| invalid(format!("invalid value `{value}` for `{field}`")))
+}
+
+fn parse_tags(value: &str) -> Result<Vec<(String, String)>> {
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+    value
+        .split(',')
+        .map(|tag| {
+            let (key, value) = tag
+                .split_once('=')
+                .ok_or_else(|| invalid(format!("invalid tag `{tag}`")))?;
+            Ok((unescape(key)?, unescape(value)?))
+        })
+        .collect()
+}
+
+/// Parses the `N` field of a way into refs and (all or no) locations.
+fn parse_nodes(value: &str, way: &mut OwnedWay) -> Result<()> {
+    if value.is_empty() {
+        return Ok(());
+    }
+    for node in value.split(',') {
+        let node = node
+            .strip_prefix('n')
+            .ok_or_else(|| invalid(format!("invalid node ref `{node}`")))?;
+        let (id, location) = match node.split_once('x') {
+            Some((id, location)) => {
+                let (lon, lat) = location
+                    .split_once('y')
+                    .ok_or_else(|| invalid(format!("invalid node location `{location}`")))?;
+                (
+                    id,
+                    Some((parse_coordinate('y', lat)?, parse_coordinate('x', lon)?)),
+                )
+            }
+            None => (node, None),
+        };
+        way.refs.push(NodeId(parse_value('N', id)?));
+        match location {
+            Some(location) if way.locations.len() + 1 == way.refs.len() => {
+                way.locations.push(location)
+            }
+            None if way.locations.is_empty() => {}
+            _ => return Err(invalid("either all or no node refs must have locations")),
+        }
+    }
+    Ok(())
+}
+
+fn parse_members(value: &str) -> Result<Vec<OwnedMember>> {
+    if value.is_empty() {
+        return Ok(Vec::new());
+    }
+    value
+        .split(',')
+        .map(|member| {
+            let (id, role) = member
+                .split_once('@')
+                .ok_or_else(|| invalid(format!("invalid member `{member}`")))?;
+            let role = unescape(role)?;
+            let mut chars = id.chars();
+            let t = chars.next();
+            let id: i64 = parse_value('M', chars.as_str())?;
+            match t {
+                Some('n') => Ok(OwnedMember::Node(NodeId(id), role)),
+                Some('w') => Ok(OwnedMember::Way(WayId(id), role)),
+                Some('r') => Ok(OwnedMember::Relation(RelationId(id), role)),
+                _ => Err(invalid(format!("invalid member `{member}`"))),
+            }
+        })
+        .collect()
+}
+
+/// Parses a single line of OPL.
+///
+/// Missing fields get their default values. The fields of changesets other
+/// than the id are ignored.
+pub fn parse_line(line: &str) -> Result<OwnedPrimitive> {
+    let mut fields = line.split(' ').filter(|f| !f.is_empty());
+    let first = fields.next().ok_or_else(|| invalid("empty line"))?;
+    let mut chars = first.chars();
+    let t = chars.next().unwrap_or_default();
+    let id: i64 = parse_value(t, chars.as_str())?;
+    if t == 'c' {
+        return Ok(OwnedPrimitive::ChangeSet(ChangeSet {
+            id: ChangeSetId(id),
+        }));
+    }
+    if !matches!(t, 'n' | 'w' | 'r') {
+        return Err(invalid(format!("unknown element type `{t}`")));
+    }
+
+    let mut meta = OwnedMeta::default();
+    let mut tags = Vec::new();
+    let (mut nano_lat, mut nano_lon) = (0, 0);
+    let mut way = OwnedWay {
+        id: WayId(id),
+        refs: Vec::new(),
+        locations: Vec::new(),
+        tags: Vec::new(),
+        meta: OwnedMeta::default(),
+    };
+    let mut members = Vec::new();
+    for field in fields {
+        let mut chars = field.chars();
+        let key = chars.next().unwrap_or_default();
+        let value = chars.as_str();
+        match (key, t) {
+            ('v', _) if !value.is_empty() => meta.version = parse_value(key, value)?,
+            ('d', _) => {
+                meta.visible = match value {
+                    "V" | "" => true,
+                    "D" => false,
+                    _ => return Err(invalid(format!("invalid value `{value}` for `d`"))),
+                }
+            }
+            ('c', _) if !value.is_empty() => meta.changeset = ChangeSetId(parse_value(key, value)?),
+            ('t', _) if !value.is_empty() => {
+                meta.timestamp = parse_timestamp(value)
+                    .ok_or_else(|| invalid(format!("invalid value `{value}` for `t`")))?;
+            }
+            ('i', _) if !value.is_empty() => meta.uid = parse_value(key, value)?,
+            ('u', _) => meta.user = unescape(value)?,
+            ('T', _) => tags = parse_tags(value)?,
+            ('x', 'n') if !value.is_empty() => nano_lon = parse_coordinate(key, value)?,
+            ('y', 'n') if !value.is_empty() => nano_lat = parse_coordinate(key, value)?,
+            ('N', 'w') => parse_nodes(value, &mut way)?,
+            ('M', 'r') => members = parse_members(value)?,
+            ('v' | 'c' | 't' | 'i' | 'x' | 'y', _) if value.is_empty() => {}
+            _ => return Err(invalid(format!("unknown field `{field}`"))),
+        }
+    }
+
+    Ok(match t {
+        'n' => OwnedPrimitive::Node(OwnedNode {
+            id: NodeId(id),
+            nano_lat,
+            nano_lon,
+            tags,
+            meta,
+        }),
+        'w' => OwnedPrimitive::Way(OwnedWay { tags, meta, ..way }),
+        _ => OwnedPrimitive::Relation(OwnedRelation {
+            id: RelationId(id),
+            members,
+            tags,
+            meta,
+        }),
+    })
+}
+
+/// Reads the elements of an OPL file as [`OwnedPrimitive`]s.
+///
+/// Empty lines and lines starting with `#` are skipped.
+pub struct OplReader<R> {
+    read: R,
+    line: String,
+    line_number: usize,
+    done: bool,
+}
+
+impl OplReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> OplReader<R> {
+    #[inline]
+    pub fn new(read: R) -> Self {
+        Self {
+            read,
+            line: String::new(),
+            line_number: 0,
+            done: false,
+        }
+    }
+
+    fn read_next(&mut self) -> Result<Option<OwnedPrimitive>> {
+        loop {
+            self.line.clear();
+            if self.read.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+            self.line_number += 1;
+            let line = self.line.trim_end_matches(['\n', '\r']);
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            return parse_line(line).map(Some).map_err(|e| match e {
+                Error::InvalidOpl(message) => {
+                    Error::InvalidOpl(format!("line {}: {message}", self.line_number))
+                }
+                e => e,
+            });
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for OplReader<R> {
+    type Item = Result<OwnedPrimitive>;
+
+    fn next(&mut self) -> Option<Result<OwnedPrimitive>> {
+        if self.done {
+            return None;
+        }
+        match self.read_next() {
+            Ok(Some(primitive)) => Some(Ok(primitive)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // don't return the same error again
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> FusedIterator for OplReader<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::Blobs;
+    use crate::data::primitive::OwnedPrimitives;
+    use crate::header::HeaderBlock;
+    use crate::writer::PbfWriter;
+
+    /// Lines as written by osmium, with escapes and deleted elements.
+    const OPL: &str = "\
+n1 v1 dV c10 t2012-12-24T18:30:00Z i5 ufoo%20%bar Tamenity=bench,name=Caf\u{e9}%20%%2c%%20%%20ac%5,a%3d%b=%25% x8.7 y49.4
+n2 v2 dD c11 t2012-12-25T00:00:00Z i5 ufoo%20%bar T x y
+n3 v1 dV c10 t2012-12-24T18:30:00Z i0 u T x-0.0000001 y-90
+w4 v1 dV c10 t2012-12-24T18:30:00Z i5 ufoo%20%bar Thighway=primary,note=%0a% Nn1,n3
+w5 v3 dD c12 t2013-01-01T00:00:00Z i6 ubaz T N
+r6 v1 dV c10 t2012-12-24T18:30:00Z i5 ufoo%20%bar Ttype=route Mw4@,n1@stop%20%1,r7@%40%
+r7 v2 dD c12 t2013-01-01T00:00:00Z i6 ubaz T M
+";
+
+    fn parse(opl: &str) -> Vec<OwnedPrimitive> {
+        OplReader::new(opl.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn parse_escapes_and_deletions() {
+        let primitives = parse(OPL);
+        assert_eq!(primitives.len(), 7);
+        let OwnedPrimitive::Node(node) = &primitives[0] else {
+            panic!("expected a node");
+        };
+        assert_eq!(node.meta.user, "foo bar");
+        assert_eq!(
+            node.tags,
+            [
+                ("amenity".to_string(), "bench".to_string()),
+                ("name".to_string(), "Caf\u{e9} , \u{20ac}5".to_string()),
+                ("a=b".to_string(), "%".to_string()),
+            ]
+        );
+        assert_eq!(
+            (node.nano_lat, node.nano_lon),
+            (49_400_000_000, 8_700_000_000)
+        );
+        for deleted in [1, 4, 6] {
+            assert!(!primitives[deleted].meta().unwrap().visible);
+        }
+        let OwnedPrimitive::Relation(relation) = &primitives[5] else {
+            panic!("expected a relation");
+        };
+        assert_eq!(
+            relation.members,
+            [
+                OwnedMember::Way(WayId(4), String::new()),
+                OwnedMember::Node(NodeId(1), "stop 1".to_string()),
+                OwnedMember::Relation(RelationId(7), "@".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn write_opl_round_trip() {
+        let mut writer = OplWriter::new(Vec::new());
+        for primitive in parse(OPL) {
+            writer.write_owned(&primitive).unwrap();
+        }
+        assert_eq!(String::from_utf8(writer.finish().unwrap()).unwrap(), OPL);
+    }
+
+    #[test]
+    fn pbf_round_trip() {
+        let primitives = parse(OPL);
+        let mut writer = PbfWriter::new(Vec::new(), &HeaderBlock::new()).unwrap();
+        for primitive in &primitives {
+            writer.write_owned(primitive).unwrap();
+        }
+        let blobs = Blobs::from_bytes(writer.finish().unwrap());
+        let read: Vec<_> = OwnedPrimitives::new(blobs).collect::<Result<_>>().unwrap();
+        assert_eq!(read, primitives);
+    }
+
+    #[test]
+    fn invalid_lines() {
+        for line in [
+            "n1 t2012-12-24T24:00:00Z",
+            "n1 t2012-02-30T00:00:00Z",
+            "n1 Tname=%zz%",
+            "n1 Tname=%20",
+            "n1 dX",
+            "x1",
+            "w1 Nn1x1y1,n2",
+        ] {
+            assert!(
+                matches!(parse_line(line), Err(Error::InvalidOpl(_))),
+                "{line}"
+            );
+        }
+        let e = OplReader::new("n1\n\nn2 v-1\n".as_bytes())
+            .find_map(Result::err)
+            .unwrap();
+        assert!(matches!(e, Error::InvalidOpl(m) if m.starts_with("line 3:")));
+    }
+}
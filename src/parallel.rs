@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::iter::FusedIterator;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+use rayon::ThreadPool;
+
+use crate::blob::Blobs;
+use crate::data::PrimitiveBlock;
+use crate::error::Result;
+
+const DEFAULT_QUEUE_SIZE: usize = 64;
+
+type Decoded = (usize, Result<PrimitiveBlock>);
+
+/// Decodes the data blobs of a file in parallel.
+///
+/// The blobs are read on a dedicated thread and decompressed and parsed on a
+/// `rayon` thread pool. The number of blobs that are in flight (being decoded
+/// or waiting to be consumed) is bounded by the queue size.
+///
+/// Created by [`Blobs::par_decode`]. The threads are started on the first
+/// call to `next`.
+pub struct ParallelDecoder<R> {
+    state: State<R>,
+    ordered: bool,
+    queue_size: usize,
+    pool: Option<Arc<ThreadPool>>,
+}
+
+enum State<R> {
+    Pending(Blobs<R>),
+    Running(Running),
+    Done,
+}
+
+struct Running {
+    results: mpsc::Receiver<Decoded>,
+    slots: mpsc::Receiver<()>,
+    pending: BTreeMap<usize, Result<PrimitiveBlock>>,
+    next_index: usize,
+}
+
+impl<R: io::BufRead + Send + 'static> Blobs<R> {
+    /// Decodes the data blobs in parallel, see [`ParallelDecoder`].
+    #[inline]
+    pub fn par_decode(self) -> ParallelDecoder<R> {
+        ParallelDecoder {
+            state: State::Pending(self),
+            ordered: true,
+            queue_size: DEFAULT_QUEUE_SIZE,
+            pool: None,
+        }
+    }
+}
+
+impl<R> ParallelDecoder<R> {
+    /// Whether the blocks are returned in file order (default: `true`).
+    ///
+    /// When `false`, blocks are returned as soon as they are decoded.
+    #[inline]
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+
+    /// Sets the maximum number of blobs in flight (default: `64`).
+    #[inline]
+    pub fn with_queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size.max(1);
+        self
+    }
+
+    /// Decodes the blobs on the given thread pool instead of the global one.
+    #[inline]
+    pub fn with_thread_pool(mut self, pool: Arc<ThreadPool>) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+}
+
+impl<R: io::BufRead + Send + 'static> ParallelDecoder<R> {
+    fn start(&mut self, blobs: Blobs<R>) -> Running {
+        let (results_tx, results) = mpsc::channel();
+        // acts as a semaphore: the reader acquires a slot for every blob,
+        // the consumer releases it after the decoded block was taken.
+        let (slots_tx, slots) = mpsc::sync_channel(self.queue_size);
+        let pool = self.pool.clone();
+        thread::spawn(move || {
+            for (index, blob) in blobs.enumerate() {
+                if slots_tx.send(()).is_err() {
+                    return; // consumer was dropped
+                }
+                let blob = match blob {
+                    Ok(blob) => blob,
+                    Err(e) => {
+                        let _ = results_tx.send((index, Err(e)));
+                        return;
+                    }
+                };
+                let results_tx = results_tx.clone();
+                let job = move || {
+                    let _ = results_tx.send((index, blob.decode()));
+                };
+                match pool {
+                    Some(ref pool) => pool.spawn(job),
+                    None => rayon::spawn(job),
+                }
+            }
+        });
+        Running {
+            results,
+            slots,
+            pending: BTreeMap::new(),
+            next_index: 0,
+        }
+    }
+}
+
+impl Running {
+    fn next(&mut self, ordered: bool) -> Option<Result<PrimitiveBlock>> {
+        let result = if ordered {
+            loop {
+                if let Some(result) = self.pending.remove(&self.next_index) {
+                    break result;
+                }
+                // all senders are dropped when the reader and all jobs are finished
+                let (index, result) = self.results.recv().ok()?;
+                self.pending.insert(index, result);
+            }
+        } else {
+            self.results.recv().ok()?.1
+        };
+        self.next_index += 1;
+        let _ = self.slots.try_recv();
+        Some(result)
+    }
+}
+
+impl<R: io::BufRead + Send + 'static> Iterator for ParallelDecoder<R> {
+    type Item = Result<PrimitiveBlock>;
+
+    fn next(&mut self) -> Option<Result<PrimitiveBlock>> {
+        if let State::Pending(_) = self.state {
+            let State::Pending(blobs) = std::mem::replace(&mut self.state, State::Done) else {
+                unreachable!()
+            };
+            self.state = State::Running(self.start(blobs));
+        }
+        let State::Running(ref mut running) = self.state else {
+            return None;
+        };
+        let result = running.next(self.ordered);
+        if matches!(result, None | Some(Err(_))) {
+            // stop after the first error
+            self.state = State::Done;
+        }
+        result
+    }
+}
+
+impl<R: io::BufRead + Send + 'static> FusedIterator for ParallelDecoder<R> {}
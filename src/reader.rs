@@ -0,0 +1,215 @@
+use std::io;
+
+use crate::blob::Blobs;
+use crate::data::{
+    node::{Node, NodeId},
+    primitive::{Primitive, PrimitiveType},
+    relation::{Relation, RelationId},
+    way::{Way, WayId},
+    ElementId, PrimitiveBlock,
+};
+use crate::error::{Error, Result};
+use crate::header::{HeaderBlock, OptionalFeatures};
+use crate::index::{search_blocks, BlobIndex, IdRange};
+
+/// Random access to the elements of a file.
+///
+/// Reads the header and builds a [`BlobIndex`] on construction. Decoded
+/// blocks are cached, so consecutive lookups in the same block are cheap.
+pub struct Reader<R> {
+    blobs: Blobs<R>,
+    header: HeaderBlock,
+    index: BlobIndex,
+    /// indices of the data blobs in `index`
+    data_blobs: Vec<usize>,
+    /// id ranges of the data blobs, `None` until the blob has been probed
+    /// and `Some(None)` for blobs without elements
+    id_ranges: Vec<Option<Option<IdRange>>>,
+    block: Option<(usize, PrimitiveBlock)>,
+}
+
+impl<R: io::BufRead + io::Seek> Reader<R> {
+    pub fn new(mut blobs: Blobs<R>) -> Result<Self> {
+        let header = blobs.header()?.decode()?;
+        let index = blobs.index()?;
+        let data_blobs: Vec<usize> = index.data_blobs().map(|(i, _)| i).collect();
+        let id_ranges = data_blobs
+            .iter()
+            .map(|&i| index[i].id_range.map(Some))
+            .collect();
+        Ok(Self {
+            blobs,
+            header,
+            index,
+            data_blobs,
+            id_ranges,
+            block: None,
+        })
+    }
+
+    #[inline]
+    pub fn header(&self) -> &HeaderBlock {
+        &self.header
+    }
+
+    #[inline]
+    pub fn index(&self) -> &BlobIndex {
+        &self.index
+    }
+
+    #[inline]
+    pub fn into_blobs(self) -> Blobs<R> {
+        self.blobs
+    }
+
+    /// Decodes the `i`-th data blob (or takes it from the cache).
+    fn block(&mut self, i: usize) -> Result<&PrimitiveBlock> {
+        if !matches!(self.block, Some((cached, _)) if cached == i) {
+            let offset = self.index[self.data_blobs[i]].offset;
+            let block = self.blobs.read_blob_at(offset)?.decode()?;
+            self.id_ranges[i] = Some(block.id_range());
+            self.block = Some((i, block));
+        }
+        Ok(&self.block.as_ref().unwrap().1)
+    }
+
+    /// Binary search over the data blobs for the blob containing the element.
+    fn find_block(&mut self, primitive_type: PrimitiveType, id: i64) -> Result<Option<usize>> {
+        if !self
+            .header
+            .optional_features()
+            .contains(OptionalFeatures::SORT_TYPE_THEN_ID)
+        {
+            return Err(Error::NotSortedByTypeThenId);
+        }
+        search_blocks(self.data_blobs.len(), (primitive_type, id), |i| {
+            if self.id_ranges[i].is_none() {
+                self.block(i)?;
+            }
+            Ok(self.id_ranges[i].flatten())
+        })
+    }
+
+    fn find<I: ElementId>(&mut self, id: I) -> Result<Option<Primitive<'_>>> {
+        let Some(i) = self.find_block(I::PRIMITIVE_TYPE, id.raw())? else {
+            return Ok(None);
+        };
+        let primitive = self
+            .block(i)?
+            .primitives()
+            .types(I::PRIMITIVE_TYPE)
+            .find(|p| match p {
+                Primitive::Node(n) => n.id.0 == id.raw(),
+                Primitive::Way(w) => w.id.0 == id.raw(),
+                Primitive::Relation(r) => r.id.0 == id.raw(),
+                Primitive::ChangeSet(c) => c.id.0 == id.raw(),
+            });
+        Ok(primitive)
+    }
+
+    /// Finds a node in a file sorted by type and id.
+    ///
+    /// Fails with [`Error::NotSortedByTypeThenId`] when the header does not
+    /// declare `Sort.Type_then_ID`.
+    pub fn find_node(&mut self, id: NodeId) -> Result<Option<Node<'_>>> {
+        match self.find(id)? {
+            Some(Primitive::Node(n)) => Ok(Some(n)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Finds a way in a file sorted by type and id.
+    pub fn find_way(&mut self, id: WayId) -> Result<Option<Way<'_>>> {
+        match self.find(id)? {
+            Some(Primitive::Way(w)) => Ok(Some(w)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Finds a relation in a file sorted by type and id.
+    pub fn find_relation(&mut self, id: RelationId) -> Result<Option<Relation<'_>>> {
+        match self.find(id)? {
+            Some(Primitive::Relation(r)) => Ok(Some(r)),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{builder::PrimitiveBlockBuilder, Meta};
+    use crate::writer::PbfWriter;
+
+    /// Data blobs with nodes 1-3, nodes 10-12, no elements, ways 5-6 and
+    /// relation 7.
+    fn file() -> Vec<u8> {
+        let mut header = HeaderBlock::new();
+        header.add_optional_features(OptionalFeatures::SORT_TYPE_THEN_ID);
+        let mut writer = PbfWriter::new(Vec::new(), &header).unwrap();
+        let tags: [(&str, &str); 0] = [];
+        for id in 1..4 {
+            writer
+                .write_node(NodeId(id), id * 100, 0, tags, &Meta::default())
+                .unwrap();
+        }
+        writer.flush_block().unwrap();
+        for id in 10..13 {
+            writer
+                .write_node(NodeId(id), id * 100, 0, tags, &Meta::default())
+                .unwrap();
+        }
+        writer
+            .write_block(&PrimitiveBlockBuilder::new().build())
+            .unwrap();
+        for id in 5..7 {
+            writer
+                .write_way(WayId(id), [NodeId(1)], tags, &Meta::default())
+                .unwrap();
+        }
+        writer.flush_block().unwrap();
+        writer
+            .write_relation(RelationId(7), [], tags, &Meta::default())
+            .unwrap();
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn find_with_empty_middle_blob() {
+        let mut reader = Reader::new(Blobs::from_bytes(file())).unwrap();
+        assert_eq!(reader.index().data_blobs().count(), 5);
+        for id in [1, 2, 3, 10, 11, 12] {
+            let node = reader.find_node(NodeId(id)).unwrap();
+            assert_eq!(
+                node.map(|n| (n.id, n.nano_lat)),
+                Some((NodeId(id), id * 100))
+            );
+        }
+        assert!(reader.find_node(NodeId(4)).unwrap().is_none());
+        assert!(reader.find_node(NodeId(13)).unwrap().is_none());
+        assert_eq!(
+            reader.find_way(WayId(6)).unwrap().map(|w| w.id),
+            Some(WayId(6))
+        );
+        assert!(reader.find_way(WayId(1)).unwrap().is_none());
+        assert_eq!(
+            reader.find_relation(RelationId(7)).unwrap().map(|r| r.id),
+            Some(RelationId(7))
+        );
+        assert!(reader.find_relation(RelationId(8)).unwrap().is_none());
+    }
+
+    #[test]
+    fn find_requires_sorted_file() {
+        let mut writer = PbfWriter::new(Vec::new(), &HeaderBlock::new()).unwrap();
+        let tags: [(&str, &str); 0] = [];
+        writer
+            .write_node(NodeId(1), 0, 0, tags, &Meta::default())
+            .unwrap();
+        let mut reader = Reader::new(Blobs::from_bytes(writer.finish().unwrap())).unwrap();
+        assert!(matches!(
+            reader.find_node(NodeId(1)),
+            Err(Error::NotSortedByTypeThenId)
+        ));
+    }
+}
@@ -0,0 +1,207 @@
+//! Selecting elements together with the elements they reference.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use crate::blob::Blobs;
+use crate::data::{
+    filter::TagFilter,
+    primitive::{Primitive, PrimitiveType},
+    relation::{Member, RelationId},
+};
+use crate::error::Result;
+use crate::extract::ExtractStats;
+use crate::id_set::{NodeIdSet, RelationIdSet, WayIdSet};
+use crate::writer::PbfWriter;
+
+/// Ids of selected nodes, ways and relations.
+///
+/// Like `osmium getid --add-referenced`: select elements (by id, tags or any
+/// predicate), add the referenced elements with [`Selection::add_referenced`]
+/// and write the referentially complete subset with [`Selection::write`].
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    pub nodes: NodeIdSet,
+    pub ways: WayIdSet,
+    pub relations: RelationIdSet,
+}
+
+/// Calls `f` for all elements of the given types in the blobs.
+fn scan<R: io::BufRead + io::Seek>(
+    blobs: &mut Blobs<R>,
+    types: PrimitiveType,
+    mut f: impl FnMut(Primitive<'_>) -> Result<()>,
+) -> Result<()> {
+    blobs.rewind()?;
+    for blob in &mut *blobs {
+        let block = blob?.decode()?;
+        for primitive in block.primitives().types(types) {
+            f(primitive)?;
+        }
+    }
+    Ok(())
+}
+
+impl Selection {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty() && self.ways.is_empty() && self.relations.is_empty()
+    }
+
+    pub fn contains(&self, primitive: &Primitive<'_>) -> bool {
+        match primitive {
+            Primitive::Node(n) => self.nodes.contains(n.id),
+            Primitive::Way(w) => self.ways.contains(w.id),
+            Primitive::Relation(r) => self.relations.contains(r.id),
+            Primitive::ChangeSet(_) => false,
+        }
+    }
+
+    /// Adds the element to the selection (changesets are ignored).
+    pub fn insert(&mut self, primitive: &Primitive<'_>) {
+        match primitive {
+            Primitive::Node(n) => self.nodes.insert(n.id),
+            Primitive::Way(w) => self.ways.insert(w.id),
+            Primitive::Relation(r) => self.relations.insert(r.id),
+            Primitive::ChangeSet(_) => false,
+        };
+    }
+
+    /// Selects all elements matching the predicate (one pass).
+    pub fn select<R>(
+        &mut self,
+        blobs: &mut Blobs<R>,
+        mut predicate: impl FnMut(&Primitive<'_>) -> bool,
+    ) -> Result<()>
+    where
+        R: io::BufRead + io::Seek,
+    {
+        scan(blobs, PrimitiveType::DEFAULT, |p| {
+            if predicate(&p) {
+                self.insert(&p);
+            }
+            Ok(())
+        })
+    }
+
+    /// Selects all elements of the given types with tags matching the
+    /// filter (one pass).
+    pub fn select_tags<R>(
+        &mut self,
+        blobs: &mut Blobs<R>,
+        types: PrimitiveType,
+        filter: &TagFilter,
+    ) -> Result<()>
+    where
+        R: io::BufRead + io::Seek,
+    {
+        blobs.rewind()?;
+        for blob in &mut *blobs {
+            let block = blob?.decode()?;
+            for primitive in block.primitives().types(types).filter_tags(filter) {
+                self.insert(&primitive);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds all elements referenced by the selected relations (recursively)
+    /// and ways.
+    ///
+    /// Needs up to three passes: one for the nested relations, one for the
+    /// members of the relations and one for the nodes of the ways.
+    pub fn add_referenced<R>(&mut self, blobs: &mut Blobs<R>) -> Result<()>
+    where
+        R: io::BufRead + io::Seek,
+    {
+        if !self.relations.is_empty() {
+            // nested relations
+            let mut children: HashMap<RelationId, Vec<RelationId>> = HashMap::new();
+            scan(blobs, PrimitiveType::RELATION, |p| {
+                if let Primitive::Relation(r) = p {
+                    let nested: Vec<RelationId> = r
+                        .members()
+                        .filter_map(|m| match m {
+                            Member::Relation(id, _) => Some(id),
+                            _ => None,
+                        })
+                        .collect();
+                    if !nested.is_empty() {
+                        children.insert(r.id, nested);
+                    }
+                }
+                Ok(())
+            })?;
+            let mut pending: Vec<RelationId> = self.relations.iter().collect();
+            while let Some(id) = pending.pop() {
+                for &child in children.get(&id).into_iter().flatten() {
+                    if self.relations.insert(child) {
+                        pending.push(child);
+                    }
+                }
+            }
+            drop(children);
+
+            // node and way members
+            let (relations, nodes, ways) = (&self.relations, &mut self.nodes, &mut self.ways);
+            scan(blobs, PrimitiveType::RELATION, |p| {
+                if let Primitive::Relation(r) = p {
+                    if relations.contains(r.id) {
+                        for member in r.members() {
+                            match member {
+                                Member::Node(id, _) => nodes.insert(id),
+                                Member::Way(id, _) => ways.insert(id),
+                                Member::Relation(..) => false,
+                            };
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+        }
+
+        if !self.ways.is_empty() {
+            // nodes of the ways
+            let (ways, nodes) = (&self.ways, &mut self.nodes);
+            scan(blobs, PrimitiveType::WAY, |p| {
+                if let Primitive::Way(w) = p {
+                    if ways.contains(w.id) {
+                        nodes.extend(w.refs());
+                    }
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Writes the selected elements (one pass).
+    pub fn write<R, W>(
+        &self,
+        blobs: &mut Blobs<R>,
+        writer: &mut PbfWriter<W>,
+    ) -> Result<ExtractStats>
+    where
+        R: io::BufRead + io::Seek,
+        W: Write,
+    {
+        let mut stats = ExtractStats::default();
+        scan(blobs, PrimitiveType::DEFAULT, |p| {
+            let counter = match &p {
+                Primitive::Node(n) if self.nodes.contains(n.id) => &mut stats.nodes,
+                Primitive::Way(w) if self.ways.contains(w.id) => &mut stats.ways,
+                Primitive::Relation(r) if self.relations.contains(r.id) => &mut stats.relations,
+                _ => return Ok(()),
+            };
+            writer.write_primitive(&p)?;
+            *counter += 1;
+            Ok(())
+        })?;
+        Ok(stats)
+    }
+}
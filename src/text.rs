@@ -0,0 +1,194 @@
+//! Textual representations of coordinates and timestamps, as used by the
+//! OSM XML and OPL formats.
+
+/// Parses a decimal coordinate like `52.5170365` to nanodegrees, without
+/// rounding errors.
+pub fn parse_nano_degrees(s: &str) -> Option<i64> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(s) => (true, s),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+    let mut value: i64 = 0;
+    for c in int.bytes() {
+        value = value
+            .checked_mul(10)?
+            .checked_add(char::from(c).to_digit(10)? as i64)?;
+    }
+    let mut scale = 1_000_000_000i64;
+    value = value.checked_mul(scale)?;
+    for c in frac.bytes() {
+        let digit = char::from(c).to_digit(10)? as i64;
+        scale /= 10;
+        value += digit * scale;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Formats nanodegrees as a decimal coordinate without trailing zeros.
+pub fn format_nano_degrees(nano: i64) -> String {
+    let sign = if nano < 0 { "-" } else { "" };
+    let nano = nano.unsigned_abs();
+    let (int, frac) = (nano / 1_000_000_000, nano % 1_000_000_000);
+    if frac == 0 {
+        return format!("{sign}{int}");
+    }
+    let frac = format!("{frac:09}");
+    format!("{sign}{int}.{}", frac.trim_end_matches('0'))
+}
+
+/// Days since `1970-01-01` for a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// The date `(year, month, day)` for days since `1970-01-01`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Formats milliseconds since the unix epoch like `2012-12-24T18:30:00Z`.
+pub fn format_timestamp(millis: i64) -> String {
+    let secs = millis.div_euclid(1000);
+    let (days, secs) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Parses an unsigned decimal number of ascii digits only.
+fn parse_digits(s: &str) -> Option<u32> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Number of days in the month of the proleptic Gregorian calendar.
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses a timestamp like `2012-12-24T18:30:00Z` to milliseconds since
+/// the unix epoch.
+///
+/// Fractional seconds (`18:30:00.250Z`) are truncated to milliseconds.
+/// Returns `None` for malformed timestamps and out of range fields.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    let s = s.strip_suffix('Z').unwrap_or(s);
+    let (date, time) = s.split_once('T')?;
+    let negative = date.starts_with('-');
+    let (year, date) = date[negative as usize..].split_once('-')?;
+    let year = parse_digits(year)? as i64;
+    let year = if negative { -year } else { year };
+    let (month, day) = date.split_once('-')?;
+    let (month, day) = (parse_digits(month)?, parse_digits(day)?);
+    let mut time = time.splitn(3, ':');
+    let hour = parse_digits(time.next()?)?;
+    let minute = parse_digits(time.next()?)?;
+    let second = time.next()?;
+    let (second, frac) = match second.split_once('.') {
+        Some((second, frac)) => (second, Some(frac)),
+        None => (second, None),
+    };
+    let second = parse_digits(second)?;
+    if !(1..=12).contains(&month)
+        || !(1..=days_in_month(year, month)).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    let mut millis = 0;
+    if let Some(frac) = frac {
+        if frac.is_empty() || !frac.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut scale = 100;
+        for digit in frac.bytes().take(3) {
+            millis += (digit - b'0') as i64 * scale;
+            scale /= 10;
+        }
+    }
+    let days = days_from_civil(year, month, day);
+    let secs = ((days * 24 + hour as i64) * 60 + minute as i64) * 60 + second as i64;
+    Some(secs * 1000 + millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamp_round_trip() {
+        for millis in [0, 1_356_373_800_000, 951_782_400_000, -86_400_000] {
+            assert_eq!(parse_timestamp(&format_timestamp(millis)), Some(millis));
+        }
+        assert_eq!(
+            parse_timestamp("2012-12-24T18:30:00Z"),
+            Some(1_356_373_800_000)
+        );
+    }
+
+    #[test]
+    fn timestamp_fractional_seconds() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:01.5Z"), Some(1500));
+        assert_eq!(parse_timestamp("1970-01-01T00:00:01.250Z"), Some(1250));
+        assert_eq!(parse_timestamp("1970-01-01T00:00:01.999999Z"), Some(1999));
+        assert_eq!(parse_timestamp("1970-01-01T00:00:01.Z"), None);
+        assert_eq!(parse_timestamp("1970-01-01T00:00:01.5xZ"), None);
+    }
+
+    #[test]
+    fn timestamp_out_of_range() {
+        for s in [
+            "2012-00-24T18:30:00Z",
+            "2012-13-24T18:30:00Z",
+            "2012-12-00T18:30:00Z",
+            "2012-12-32T18:30:00Z",
+            "2013-02-29T18:30:00Z",
+            "1900-02-29T18:30:00Z",
+            "2012-04-31T18:30:00Z",
+            "2012-12-24T24:00:00Z",
+            "2012-12-24T18:60:00Z",
+            "2012-12-24T18:30:60Z",
+            "2012-12-24T-1:30:00Z",
+            "2012-12-24T+1:30:00Z",
+            "2012-12-24T18:30Z",
+            "2012-12-24",
+            "",
+        ] {
+            assert_eq!(parse_timestamp(s), None, "{s}");
+        }
+        assert!(parse_timestamp("2000-02-29T00:00:00Z").is_some());
+        assert!(parse_timestamp("2012-12-24T23:59:59Z").is_some());
+    }
+}
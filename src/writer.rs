@@ -0,0 +1,314 @@
+use std::io;
+
+use crate::blob::{BlobWriter, Compression};
+use crate::data::{
+    builder::PrimitiveBlockBuilder,
+    changeset::ChangeSetId,
+    node::NodeId,
+    primitive::{OwnedPrimitive, Primitive},
+    relation::{Member, RelationId},
+    way::WayId,
+    Meta, OSMDataBlob, PrimitiveBlock,
+};
+use crate::error::Result;
+use crate::header::{HeaderBlock, OSMHeaderBlob};
+
+/// Maximum number of elements per block, as recommended by the
+/// [PBF Format](https://wiki.openstreetmap.org/wiki/PBF_Format).
+const DEFAULT_MAX_BLOCK_LEN: usize = 8000;
+
+/// Writes an `.osm.pbf` file.
+///
+/// The header block is written on construction. Elements are collected
+/// into [`PrimitiveBlock`]s that are written when they are full. Call
+/// [`PbfWriter::finish`] to write the last block.
+pub struct PbfWriter<W: io::Write> {
+    blobs: BlobWriter<W>,
+    block: PrimitiveBlockBuilder,
+    max_block_len: usize,
+}
+
+impl<W: io::Write> PbfWriter<W> {
+    /// Creates a new writer with the default compression and writes the header.
+    #[inline]
+    pub fn new(write: W, header: &HeaderBlock) -> Result<Self> {
+        Self::from_blob_writer(BlobWriter::new(write), header)
+    }
+
+    /// Creates a new writer with the given compression and writes the header.
+    #[inline]
+    pub fn with_compression(
+        write: W,
+        compression: Compression,
+        header: &HeaderBlock,
+    ) -> Result<Self> {
+        Self::from_blob_writer(BlobWriter::new(write).with_compression(compression), header)
+    }
+
+    /// Creates a new writer on top of the given blob writer and writes the header.
+    pub fn from_blob_writer(mut blobs: BlobWriter<W>, header: &HeaderBlock) -> Result<Self> {
+        blobs.write_block(header)?;
+        Ok(Self::from_blob_writer_without_header(blobs))
+    }
+
+    /// Creates a new writer and writes the header blob unchanged.
+    pub fn from_header_blob(mut blobs: BlobWriter<W>, header: &OSMHeaderBlob) -> Result<Self> {
+        blobs.write_blob(header)?;
+        Ok(Self::from_blob_writer_without_header(blobs))
+    }
+
+    #[inline]
+    fn from_blob_writer_without_header(blobs: BlobWriter<W>) -> Self {
+        Self {
+            blobs,
+            block: PrimitiveBlockBuilder::new(),
+            max_block_len: DEFAULT_MAX_BLOCK_LEN,
+        }
+    }
+
+    /// Sets the builder used for the following blocks.
+    ///
+    /// Use this to change the granularity or offset of the coordinates.
+    pub fn with_block_builder(mut self, block: PrimitiveBlockBuilder) -> Result<Self> {
+        self.flush_block()?;
+        self.block = block;
+        Ok(self)
+    }
+
+    /// Sets the maximum number of elements per block (default: `8000`).
+    #[inline]
+    pub fn with_max_block_len(mut self, max_block_len: usize) -> Self {
+        self.max_block_len = max_block_len.max(1);
+        self
+    }
+
+    #[inline]
+    fn after_add(&mut self) -> Result<()> {
+        if self.block.len() >= self.max_block_len {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    /// Adds a node with the given coordinates in nanodegrees.
+    pub fn write_node<K, V>(
+        &mut self,
+        id: NodeId,
+        nano_lat: i64,
+        nano_lon: i64,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.block.add_node(id, nano_lat, nano_lon, tags, meta);
+        self.after_add()
+    }
+
+    /// Adds a way referencing the given nodes.
+    pub fn write_way<K, V>(
+        &mut self,
+        id: WayId,
+        refs: impl IntoIterator<Item = NodeId>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.block.add_way(id, refs, tags, meta);
+        self.after_add()
+    }
+
+    /// Adds a relation with the given members.
+    pub fn write_relation<'m, K, V>(
+        &mut self,
+        id: RelationId,
+        members: impl IntoIterator<Item = Member<'m>>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.block.add_relation(id, members, tags, meta);
+        self.after_add()
+    }
+
+    /// Adds a changeset.
+    pub fn write_changeset(&mut self, id: ChangeSetId) -> Result<()> {
+        self.block.add_changeset(id);
+        self.after_add()
+    }
+
+    /// Adds a copy of the given primitive.
+    pub fn write_primitive(&mut self, primitive: &Primitive<'_>) -> Result<()> {
+        self.block.add_primitive(primitive);
+        self.after_add()
+    }
+
+    /// Adds a copy of the given owned primitive.
+    pub fn write_owned(&mut self, primitive: &OwnedPrimitive) -> Result<()> {
+        self.block.add_owned(primitive);
+        self.after_add()
+    }
+
+    /// Writes a complete block after the pending elements.
+    pub fn write_block(&mut self, block: &PrimitiveBlock) -> Result<()> {
+        self.flush_block()?;
+        self.blobs.write_block(block)
+    }
+
+    /// Writes a blob unchanged after the pending elements.
+    pub fn write_blob(&mut self, blob: &OSMDataBlob) -> Result<()> {
+        self.flush_block()?;
+        self.blobs.write_blob(blob)
+    }
+
+    /// Writes the pending elements as a block.
+    pub fn flush_block(&mut self) -> Result<()> {
+        if !self.block.is_empty() {
+            let block = self.block.build();
+            self.blobs.write_block(&block)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the pending elements and flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        self.flush_block()?;
+        self.blobs.flush()
+    }
+
+    /// Writes the pending elements and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.flush()?;
+        Ok(self.blobs.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::blob::Blobs;
+    use crate::data::{
+        node::OwnedNode,
+        primitive::OwnedPrimitives,
+        relation::{OwnedMember, OwnedRelation},
+        way::OwnedWay,
+        OwnedMeta,
+    };
+
+    fn read(bytes: Vec<u8>) -> Vec<OwnedPrimitive> {
+        OwnedPrimitives::new(Blobs::from_bytes(bytes))
+            .collect::<Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let meta = OwnedMeta {
+            version: 3,
+            timestamp: 1_356_373_800_000,
+            changeset: ChangeSetId(12),
+            uid: 5,
+            user: "foo".to_string(),
+            visible: true,
+        };
+        let node = |id, nano_lat, nano_lon| {
+            OwnedPrimitive::Node(OwnedNode {
+                id: NodeId(id),
+                nano_lat,
+                nano_lon,
+                tags: vec![("name".to_string(), format!("n{id}"))],
+                meta: meta.clone(),
+            })
+        };
+        let primitives = vec![
+            node(-3, -45_000_000_100, 170_000_000_000),
+            node(1, 52_000_000_000, 13_000_000_000),
+            node(2, 52_000_000_100, 12_999_999_900),
+            OwnedPrimitive::Way(OwnedWay {
+                id: WayId(4),
+                refs: vec![NodeId(2), NodeId(1), NodeId(-3), NodeId(2)],
+                locations: Vec::new(),
+                tags: Vec::new(),
+                meta: meta.clone(),
+            }),
+            OwnedPrimitive::Relation(OwnedRelation {
+                id: RelationId(5),
+                members: vec![
+                    OwnedMember::Way(WayId(4), "outer".to_string()),
+                    OwnedMember::Node(NodeId(1), String::new()),
+                    OwnedMember::Relation(RelationId(2), "sub".to_string()),
+                ],
+                tags: vec![("type".to_string(), "multipolygon".to_string())],
+                meta: OwnedMeta {
+                    visible: false,
+                    ..meta.clone()
+                },
+            }),
+        ];
+        let mut writer = PbfWriter::new(Vec::new(), &HeaderBlock::new()).unwrap();
+        for primitive in &primitives {
+            writer.write_owned(primitive).unwrap();
+        }
+        assert_eq!(read(writer.finish().unwrap()), primitives);
+    }
+
+    /// The offsets are no multiples of the default granularity, so the
+    /// locations only survive when every block uses the builder settings.
+    #[test]
+    fn keep_block_builder_across_blocks() {
+        let builder = PrimitiveBlockBuilder::new()
+            .with_granularity(1000)
+            .with_offset(-10_050, 20_070);
+        let mut writer = PbfWriter::new(Vec::new(), &HeaderBlock::new())
+            .unwrap()
+            .with_block_builder(builder)
+            .unwrap()
+            .with_max_block_len(2);
+        let tags: [(&str, &str); 0] = [];
+        for id in 1..6 {
+            writer
+                .write_node(
+                    NodeId(id),
+                    id * 1000 - 10_050,
+                    20_070 - id * 1000,
+                    tags,
+                    &Meta::default(),
+                )
+                .unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let blocks: Vec<PrimitiveBlock> = Blobs::from_bytes(&bytes)
+            .map(|blob| blob.and_then(|blob| blob.decode()))
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            blocks
+                .iter()
+                .map(|b| b.primitives().count())
+                .collect::<Vec<_>>(),
+            [2, 2, 1]
+        );
+        let nodes: Vec<_> = read(bytes)
+            .into_iter()
+            .map(|p| match p {
+                OwnedPrimitive::Node(n) => (n.id.0, n.nano_lat, n.nano_lon),
+                p => panic!("unexpected {p:?}"),
+            })
+            .collect();
+        assert_eq!(
+            nodes,
+            (1..6)
+                .map(|id| (id, id * 1000 - 10_050, 20_070 - id * 1000))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,9 @@
+//! Reading and writing OSM XML (`.osm`) and OsmChange (`.osc`) files.
+
+mod osc;
+mod reader;
+mod writer;
+
+pub use self::osc::OscReader;
+pub use self::reader::XmlReader;
+pub use self::writer::XmlWriter;
@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::iter::FusedIterator;
+use std::path::Path;
+
+use super::XmlReader;
+use crate::change::Change;
+use crate::error::{Error, Result};
+use crate::header::HeaderBlock;
+
+/// Reads the changes of an OsmChange (`.osc`) file, e.g. a replication diff.
+///
+/// Compressed diffs (`.osc.gz`) can be read through a
+/// [`flate2::bufread::GzDecoder`].
+pub struct OscReader<R> {
+    inner: XmlReader<R>,
+}
+
+impl OscReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> OscReader<R> {
+    #[inline]
+    pub fn new(read: R) -> Result<Self> {
+        Ok(Self {
+            inner: XmlReader::new(read)?,
+        })
+    }
+
+    /// Header with the generator (as writing program) of the file.
+    #[inline]
+    pub fn header(&self) -> &HeaderBlock {
+        self.inner.header()
+    }
+}
+
+impl<R: BufRead> Iterator for OscReader<R> {
+    type Item = Result<Change>;
+
+    fn next(&mut self) -> Option<Result<Change>> {
+        let element = match self.inner.next()? {
+            Ok(element) => element,
+            Err(e) => return Some(Err(e)),
+        };
+        // the action of the element that was just read
+        match self.inner.action {
+            Some(action) => Some(Ok(Change { action, element })),
+            None => Some(Err(Error::InvalidXml(
+                "element outside of <create>, <modify> or <delete>".to_string(),
+            ))),
+        }
+    }
+}
+
+impl<R: BufRead> FusedIterator for OscReader<R> {}
@@ -0,0 +1,346 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::iter::FusedIterator;
+use std::path::Path;
+use std::str::FromStr;
+
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+
+use crate::change::Action;
+use crate::data::{
+    changeset::{ChangeSet, ChangeSetId},
+    node::{NodeId, OwnedNode},
+    primitive::OwnedPrimitive,
+    relation::{OwnedMember, OwnedRelation, RelationId},
+    way::{OwnedWay, WayId},
+    OwnedMeta,
+};
+use crate::error::{Error, Result};
+use crate::header::{BoundingBox, HeaderBlock};
+use crate::text::{parse_nano_degrees, parse_timestamp};
+
+/// Reads the elements of an OSM XML file as [`OwnedPrimitive`]s.
+///
+/// The `<bounds>` and the `generator` of the file are available through
+/// [`XmlReader::header`].
+pub struct XmlReader<R> {
+    reader: Reader<R>,
+    buf: Vec<u8>,
+    header: HeaderBlock,
+    pending: Option<OwnedPrimitive>,
+    done: bool,
+    /// the current `<create>`, `<modify>` or `<delete>` block of an OsmChange file
+    pub(super) action: Option<Action>,
+}
+
+impl XmlReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: BufRead> XmlReader<R> {
+    /// Creates the reader and reads everything up to the first element.
+    pub fn new(read: R) -> Result<Self> {
+        let mut reader = Reader::from_reader(read);
+        reader.config_mut().trim_text(true);
+        let mut header = HeaderBlock::new();
+        header.clear_writingprogram();
+        let mut this = Self {
+            reader,
+            buf: Vec::new(),
+            header,
+            pending: None,
+            done: false,
+            action: None,
+        };
+        this.pending = this.read_next()?;
+        this.done = this.pending.is_none();
+        Ok(this)
+    }
+
+    /// Header with the bounding box and the generator (as writing program) of the file.
+    #[inline]
+    pub fn header(&self) -> &HeaderBlock {
+        &self.header
+    }
+
+    fn read_next(&mut self) -> Result<Option<OwnedPrimitive>> {
+        loop {
+            self.buf.clear();
+            let (start, empty) = match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(e) => (e.into_owned(), false),
+                Event::Empty(e) => (e.into_owned(), true),
+                Event::End(e) => {
+                    if matches!(e.name().as_ref(), b"create" | b"modify" | b"delete") {
+                        self.action = None;
+                    }
+                    continue;
+                }
+                Event::Eof => return Ok(None),
+                _ => continue,
+            };
+            let primitive = match start.name().as_ref() {
+                b"create" if !empty => {
+                    self.action = Some(Action::Create);
+                    continue;
+                }
+                b"modify" if !empty => {
+                    self.action = Some(Action::Modify);
+                    continue;
+                }
+                b"delete" if !empty => {
+                    self.action = Some(Action::Delete);
+                    continue;
+                }
+                b"osm" | b"osmChange" => {
+                    if let Some(generator) = attribute(&start, "generator")? {
+                        self.header.set_writing_program(&generator);
+                    }
+                    continue;
+                }
+                b"bounds" => {
+                    self.header.set_bbox(Some(parse_bounds(&start)?));
+                    continue;
+                }
+                b"bound" => {
+                    if let Some(bbox) = attribute(&start, "box")? {
+                        self.header.set_bbox(Some(parse_bound_box(&bbox)?));
+                    }
+                    continue;
+                }
+                b"node" => OwnedPrimitive::Node(self.read_node(&start, empty)?),
+                b"way" => OwnedPrimitive::Way(self.read_way(&start, empty)?),
+                b"relation" => OwnedPrimitive::Relation(self.read_relation(&start, empty)?),
+                b"changeset" => {
+                    let id = required::<i64>(&start, "id")?;
+                    if !empty {
+                        self.reader.read_to_end_into(start.name(), &mut self.buf)?;
+                    }
+                    OwnedPrimitive::ChangeSet(ChangeSet {
+                        id: ChangeSetId(id),
+                    })
+                }
+                _ => continue,
+            };
+            return Ok(Some(primitive));
+        }
+    }
+
+    /// Calls `f` for each child element until the end of the element `name`.
+    fn read_children(
+        &mut self,
+        name: &str,
+        mut f: impl FnMut(&BytesStart<'_>) -> Result<()>,
+    ) -> Result<()> {
+        loop {
+            self.buf.clear();
+            match self.reader.read_event_into(&mut self.buf)? {
+                Event::Start(e) | Event::Empty(e) => f(&e)?,
+                Event::End(e) if e.name().as_ref() == name.as_bytes() => return Ok(()),
+                Event::Eof => {
+                    return Err(Error::InvalidXml(format!(
+                        "unexpected end of file in <{name}>"
+                    )))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn read_node(&mut self, start: &BytesStart<'_>, empty: bool) -> Result<OwnedNode> {
+        let mut node = OwnedNode {
+            id: NodeId(required(start, "id")?),
+            nano_lat: 0,
+            nano_lon: 0,
+            tags: Vec::new(),
+            meta: parse_meta(start)?,
+        };
+        // deleted nodes in history files have no location
+        if let Some(lat) = attribute(start, "lat")? {
+            node.nano_lat = parse_coordinate("lat", &lat)?;
+        }
+        if let Some(lon) = attribute(start, "lon")? {
+            node.nano_lon = parse_coordinate("lon", &lon)?;
+        }
+        if !empty {
+            self.read_children("node", |e| parse_tag(e, &mut node.tags))?;
+        }
+        Ok(node)
+    }
+
+    fn read_way(&mut self, start: &BytesStart<'_>, empty: bool) -> Result<OwnedWay> {
+        let mut way = OwnedWay {
+            id: WayId(required(start, "id")?),
+            refs: Vec::new(),
+            locations: Vec::new(),
+            tags: Vec::new(),
+            meta: parse_meta(start)?,
+        };
+        if !empty {
+            self.read_children("way", |e| match e.name().as_ref() {
+                b"nd" => {
+                    way.refs.push(NodeId(required(e, "ref")?));
+                    // locations on ways, e.g. from Overpass `out geom`
+                    if let (Some(lat), Some(lon)) = (attribute(e, "lat")?, attribute(e, "lon")?) {
+                        way.locations.push((
+                            parse_coordinate("lat", &lat)?,
+                            parse_coordinate("lon", &lon)?,
+                        ));
+                    }
+                    Ok(())
+                }
+                _ => parse_tag(e, &mut way.tags),
+            })?;
+        }
+        if way.locations.len() != way.refs.len() {
+            way.locations.clear();
+        }
+        Ok(way)
+    }
+
+    fn read_relation(&mut self, start: &BytesStart<'_>, empty: bool) -> Result<OwnedRelation> {
+        let mut relation = OwnedRelation {
+            id: RelationId(required(start, "id")?),
+            members: Vec::new(),
+            tags: Vec::new(),
+            meta: parse_meta(start)?,
+        };
+        if !empty {
+            self.read_children("relation", |e| match e.name().as_ref() {
+                b"member" => {
+                    let id = required::<i64>(e, "ref")?;
+                    let role = attribute(e, "role")?.unwrap_or_default();
+                    let member = match required::<String>(e, "type")?.as_str() {
+                        "node" => OwnedMember::Node(NodeId(id), role),
+                        "way" => OwnedMember::Way(WayId(id), role),
+                        "relation" => OwnedMember::Relation(RelationId(id), role),
+                        other => {
+                            return Err(Error::InvalidXml(format!("unknown member type `{other}`")))
+                        }
+                    };
+                    relation.members.push(member);
+                    Ok(())
+                }
+                _ => parse_tag(e, &mut relation.tags),
+            })?;
+        }
+        Ok(relation)
+    }
+}
+
+impl<R: BufRead> Iterator for XmlReader<R> {
+    type Item = Result<OwnedPrimitive>;
+
+    fn next(&mut self) -> Option<Result<OwnedPrimitive>> {
+        if let Some(primitive) = self.pending.take() {
+            return Some(Ok(primitive));
+        }
+        if self.done {
+            return None;
+        }
+        match self.read_next() {
+            Ok(Some(primitive)) => Some(Ok(primitive)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                // don't return the same error again
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<R: BufRead> FusedIterator for XmlReader<R> {}
+
+fn attribute(e: &BytesStart<'_>, name: &str) -> Result<Option<String>> {
+    match e.try_get_attribute(name).map_err(quick_xml::Error::from)? {
+        Some(attr) => Ok(Some(attr.unescape_value()?.into_owned())),
+        None => Ok(None),
+    }
+}
+
+fn parse_value<T: FromStr>(name: &str, value: &str) -> Result<T> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidXml(format!("invalid value `{value}` for `{name}`")))
+}
+
+fn optional<T: FromStr>(e: &BytesStart<'_>, name: &str) -> Result<Option<T>> {
+    attribute(e, name)?
+        .map(|value| parse_value(name, &value))
+        .transpose()
+}
+
+fn required<T: FromStr>(e: &BytesStart<'_>, name: &str) -> Result<T> {
+    optional(e, name)?.ok_or_else(|| Error::InvalidXml(format!("missing attribute `{name}`")))
+}
+
+fn parse_coordinate(name: &str, value: &str) -> Result<i64> {
+    parse_nano_degrees(value)
+        .ok_or_else(|| Error::InvalidXml(format!("invalid value `{value}` for `{name}`")))
+}
+
+fn parse_meta(e: &BytesStart<'_>) -> Result<OwnedMeta> {
+    let mut meta = OwnedMeta::default();
+    if let Some(version) = optional(e, "version")? {
+        meta.version = version;
+    }
+    if let Some(timestamp) = attribute(e, "timestamp")? {
+        meta.timestamp = parse_timestamp(&timestamp).ok_or_else(|| {
+            Error::InvalidXml(format!("invalid value `{timestamp}` for `timestamp`"))
+        })?;
+    }
+    if let Some(changeset) = optional(e, "changeset")? {
+        meta.changeset = ChangeSetId(changeset);
+    }
+    if let Some(uid) = optional(e, "uid")? {
+        meta.uid = uid;
+    }
+    if let Some(user) = attribute(e, "user")? {
+        meta.user = user;
+    }
+    if let Some(visible) = optional(e, "visible")? {
+        meta.visible = visible;
+    }
+    Ok(meta)
+}
+
+fn parse_tag(e: &BytesStart<'_>, tags: &mut Vec<(String, String)>) -> Result<()> {
+    if e.name().as_ref() == b"tag" {
+        tags.push((required(e, "k")?, required(e, "v")?));
+    }
+    Ok(())
+}
+
+fn parse_bounds(e: &BytesStart<'_>) -> Result<BoundingBox> {
+    let coordinate = |name| parse_coordinate(name, &required::<String>(e, name)?);
+    Ok(BoundingBox {
+        left: coordinate("minlon")?,
+        right: coordinate("maxlon")?,
+        top: coordinate("maxlat")?,
+        bottom: coordinate("minlat")?,
+    })
+}
+
+/// Parses the `box` attribute of `<bound>` (`minlat,minlon,maxlat,maxlon`).
+fn parse_bound_box(value: &str) -> Result<BoundingBox> {
+    let values: Vec<&str> = value.split(',').collect();
+    let [minlat, minlon, maxlat, maxlon] = values[..] else {
+        return Err(Error::InvalidXml(format!(
+            "invalid value `{value}` for `box`"
+        )));
+    };
+    Ok(BoundingBox {
+        left: parse_coordinate("box", minlon)?,
+        right: parse_coordinate("box", maxlon)?,
+        top: parse_coordinate("box", maxlat)?,
+        bottom: parse_coordinate("box", minlat)?,
+    })
+}
@@ -0,0 +1,301 @@
+use std::io::Write;
+
+use crate::data::{
+    changeset::ChangeSetId,
+    node::NodeId,
+    primitive::{OwnedPrimitive, Primitive},
+    relation::{Member, OwnedMember, RelationId},
+    way::WayId,
+    Meta,
+};
+use crate::error::Result;
+use crate::header::HeaderBlock;
+use crate::text::{format_nano_degrees, format_timestamp};
+
+/// Writes elements as OSM XML.
+///
+/// The bounding box of the header is written as `<bounds>` and its writing
+/// program as the `generator`.
+pub struct XmlWriter<W: Write> {
+    write: W,
+}
+
+impl<W: Write> XmlWriter<W> {
+    /// Creates the writer and writes the `<osm>` element.
+    pub fn new(mut write: W, header: &HeaderBlock) -> Result<Self> {
+        write.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<osm version=\"0.6\"")?;
+        if let Some(generator) = header.writing_program() {
+            write_attribute(&mut write, "generator", generator)?;
+        }
+        write.write_all(b">\n")?;
+        if let Some(bbox) = header.bbox() {
+            writeln!(
+                write,
+                r#"  <bounds minlat="{}" minlon="{}" maxlat="{}" maxlon="{}"/>"#,
+                format_nano_degrees(bbox.bottom),
+                format_nano_degrees(bbox.left),
+                format_nano_degrees(bbox.top),
+                format_nano_degrees(bbox.right),
+            )?;
+        }
+        Ok(Self { write })
+    }
+
+    fn write_start(&mut self, element: &str, id: i64, meta: &Meta<'_>) -> Result<()> {
+        write!(self.write, r#"  <{element} id="{id}""#)?;
+        if meta.version != 0 {
+            write!(self.write, r#" version="{}""#, meta.version)?;
+        }
+        if meta.timestamp != 0 {
+            let timestamp = format_timestamp(meta.timestamp);
+            write!(self.write, r#" timestamp="{timestamp}""#)?;
+        }
+        if meta.changeset.0 != 0 {
+            write!(self.write, r#" changeset="{}""#, meta.changeset.0)?;
+        }
+        if meta.uid != 0 || !meta.user.is_empty() {
+            write!(self.write, r#" uid="{}""#, meta.uid)?;
+            write_attribute(&mut self.write, "user", meta.user)?;
+        }
+        if !meta.visible {
+            self.write.write_all(br#" visible="false""#)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the tags and the end of the element; `children` tells whether
+    /// the element is still open.
+    fn write_end<K, V>(
+        &mut self,
+        element: &str,
+        mut children: bool,
+        tags: impl IntoIterator<Item = (K, V)>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        for (k, v) in tags {
+            if !children {
+                self.write.write_all(b">\n")?;
+                children = true;
+            }
+            self.write.write_all(b"    <tag")?;
+            write_attribute(&mut self.write, "k", k.as_ref())?;
+            write_attribute(&mut self.write, "v", v.as_ref())?;
+            self.write.write_all(b"/>\n")?;
+        }
+        if children {
+            writeln!(self.write, "  </{element}>")?;
+        } else {
+            self.write.write_all(b"/>\n")?;
+        }
+        Ok(())
+    }
+
+    /// Writes a node with the given coordinates in nanodegrees.
+    pub fn write_node<K, V>(
+        &mut self,
+        id: NodeId,
+        nano_lat: i64,
+        nano_lon: i64,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.write_start("node", id.0, meta)?;
+        // deleted nodes have no location
+        if meta.visible {
+            write!(
+                self.write,
+                r#" lat="{}" lon="{}""#,
+                format_nano_degrees(nano_lat),
+                format_nano_degrees(nano_lon),
+            )?;
+        }
+        self.write_end("node", false, tags)
+    }
+
+    fn write_way_nodes<K, V>(
+        &mut self,
+        id: WayId,
+        nodes: impl IntoIterator<Item = (NodeId, Option<(i64, i64)>)>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.write_start("way", id.0, meta)?;
+        let mut children = false;
+        for (node, location) in nodes {
+            if !children {
+                self.write.write_all(b">\n")?;
+                children = true;
+            }
+            write!(self.write, r#"    <nd ref="{}""#, node.0)?;
+            if let Some((nano_lat, nano_lon)) = location {
+                write!(
+                    self.write,
+                    r#" lat="{}" lon="{}""#,
+                    format_nano_degrees(nano_lat),
+                    format_nano_degrees(nano_lon),
+                )?;
+            }
+            self.write.write_all(b"/>\n")?;
+        }
+        self.write_end("way", children, tags)
+    }
+
+    /// Writes a way referencing the given nodes.
+    pub fn write_way<K, V>(
+        &mut self,
+        id: WayId,
+        refs: impl IntoIterator<Item = NodeId>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.write_way_nodes(id, refs.into_iter().map(|id| (id, None)), tags, meta)
+    }
+
+    /// Writes a way with the locations `(nano_lat, nano_lon)` of its nodes
+    /// as `lat`/`lon` attributes of the `<nd>` elements.
+    pub fn write_way_with_locations<K, V>(
+        &mut self,
+        id: WayId,
+        nodes: impl IntoIterator<Item = (NodeId, (i64, i64))>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        let nodes = nodes.into_iter().map(|(id, l)| (id, Some(l)));
+        self.write_way_nodes(id, nodes, tags, meta)
+    }
+
+    /// Writes a relation with the given members.
+    pub fn write_relation<'m, K, V>(
+        &mut self,
+        id: RelationId,
+        members: impl IntoIterator<Item = Member<'m>>,
+        tags: impl IntoIterator<Item = (K, V)>,
+        meta: &Meta<'_>,
+    ) -> Result<()>
+    where
+        K: AsRef<str>,
+        V: AsRef<str>,
+    {
+        self.write_start("relation", id.0, meta)?;
+        let mut children = false;
+        for member in members {
+            if !children {
+                self.write.write_all(b">\n")?;
+                children = true;
+            }
+            let (member_type, member_id) = match member {
+                Member::Node(id, _) => ("node", id.0),
+                Member::Way(id, _) => ("way", id.0),
+                Member::Relation(id, _) => ("relation", id.0),
+            };
+            write!(
+                self.write,
+                r#"    <member type="{member_type}" ref="{member_id}""#
+            )?;
+            write_attribute(&mut self.write, "role", member.role())?;
+            self.write.write_all(b"/>\n")?;
+        }
+        self.write_end("relation", children, tags)
+    }
+
+    pub fn write_changeset(&mut self, id: ChangeSetId) -> Result<()> {
+        writeln!(self.write, r#"  <changeset id="{}"/>"#, id.0)?;
+        Ok(())
+    }
+
+    /// Writes the given primitive.
+    pub fn write_primitive(&mut self, primitive: &Primitive<'_>) -> Result<()> {
+        match primitive {
+            Primitive::Node(n) => self.write_node(n.id, n.nano_lat, n.nano_lon, n.tags(), n),
+            Primitive::Way(w) if w.has_locations() => {
+                self.write_way_with_locations(w.id, w.refs_with_locations(), w.tags(), w)
+            }
+            Primitive::Way(w) => self.write_way(w.id, w.refs(), w.tags(), w),
+            Primitive::Relation(r) => self.write_relation(r.id, r.members(), r.tags(), r),
+            Primitive::ChangeSet(c) => self.write_changeset(c.id),
+        }
+    }
+
+    /// Writes the given owned primitive.
+    pub fn write_owned(&mut self, primitive: &OwnedPrimitive) -> Result<()> {
+        match primitive {
+            OwnedPrimitive::Node(n) => self.write_node(
+                n.id,
+                n.nano_lat,
+                n.nano_lon,
+                n.tags.iter().map(|(k, v)| (k, v)),
+                &n.meta.as_meta(),
+            ),
+            OwnedPrimitive::Way(w) if !w.locations.is_empty() => self.write_way_with_locations(
+                w.id,
+                w.refs.iter().copied().zip(w.locations.iter().copied()),
+                w.tags.iter().map(|(k, v)| (k, v)),
+                &w.meta.as_meta(),
+            ),
+            OwnedPrimitive::Way(w) => self.write_way(
+                w.id,
+                w.refs.iter().copied(),
+                w.tags.iter().map(|(k, v)| (k, v)),
+                &w.meta.as_meta(),
+            ),
+            OwnedPrimitive::Relation(r) => self.write_relation(
+                r.id,
+                r.members.iter().map(OwnedMember::as_member),
+                r.tags.iter().map(|(k, v)| (k, v)),
+                &r.meta.as_meta(),
+            ),
+            OwnedPrimitive::ChangeSet(c) => self.write_changeset(c.id),
+        }
+    }
+
+    /// Writes the end of the `<osm>` element and returns the inner writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.write.write_all(b"</osm>\n")?;
+        self.write.flush()?;
+        Ok(self.write)
+    }
+}
+
+/// Writes ` name="value"` with the value escaped.
+fn write_attribute(w: &mut impl Write, name: &str, value: &str) -> Result<()> {
+    write!(w, r#" {name}=""#)?;
+    let mut start = 0;
+    for (i, c) in value.char_indices() {
+        let escaped = match c {
+            '&' => "&amp;",
+            '<' => "&lt;",
+            '>' => "&gt;",
+            '"' => "&quot;",
+            '\'' => "&apos;",
+            '\n' => "&#10;",
+            '\r' => "&#13;",
+            '\t' => "&#9;",
+            _ => continue,
+        };
+        w.write_all(&value.as_bytes()[start..i])?;
+        w.write_all(escaped.as_bytes())?;
+        start = i + 1;
+    }
+    w.write_all(&value.as_bytes()[start..])?;
+    w.write_all(b"\"")?;
+    Ok(())
+}